@@ -1,11 +1,9 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::collections::LinkedList;
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-#[derive(Eq, PartialEq, PartialOrd, Ord)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
 enum PipeType {
     NotAPipe,
     VerticalPipe,
@@ -33,16 +31,17 @@ impl std::fmt::Display for PipeType {
 }
 
 impl PipeType {
-    fn to_connections(&self) -> ((i32, i32), (i32, i32)) {
+    // None for ground and for the start, whose shape is unknown until the
+    // map is validated
+    fn to_connections(self) -> Option<((i32, i32), (i32, i32))> {
         match self {
-            PipeType::VerticalPipe => ((-1, 0), (1, 0)),
-            PipeType::HorizontalPipe => ((0, -1), (0, 1)),
-            PipeType::NorthWestPipe => ((-1, 0), (0, -1)),
-            PipeType::NorthEastPipe => ((-1, 0), (0, 1)),
-            PipeType::SouthWestPipe => ((1, 0), (0, -1)),
-            PipeType::SouthEastPipe => ((1, 0), (0, 1)),
-            PipeType::StartingPipe => ((2, 2), (2, 2)),
-            _ => ((0, 0), (0, 0)),
+            PipeType::VerticalPipe => Some(((-1, 0), (1, 0))),
+            PipeType::HorizontalPipe => Some(((0, -1), (0, 1))),
+            PipeType::NorthWestPipe => Some(((-1, 0), (0, -1))),
+            PipeType::NorthEastPipe => Some(((-1, 0), (0, 1))),
+            PipeType::SouthWestPipe => Some(((1, 0), (0, -1))),
+            PipeType::SouthEastPipe => Some(((1, 0), (0, 1))),
+            PipeType::StartingPipe | PipeType::NotAPipe => None,
         }
     }
 
@@ -60,8 +59,21 @@ impl PipeType {
         }
     }
 
+    fn to_symbol(self) -> char {
+        match self {
+            PipeType::VerticalPipe => '|',
+            PipeType::HorizontalPipe => '-',
+            PipeType::NorthEastPipe => 'L',
+            PipeType::NorthWestPipe => 'J',
+            PipeType::SouthWestPipe => '7',
+            PipeType::SouthEastPipe => 'F',
+            PipeType::StartingPipe => 'S',
+            PipeType::NotAPipe => '.',
+        }
+    }
+
     #[allow(dead_code)]
-    fn to_unicode(&self) -> char {
+    fn to_unicode(self) -> char {
         match self {
             PipeType::VerticalPipe => '│',
             PipeType::HorizontalPipe => '─',
@@ -75,19 +87,6 @@ impl PipeType {
         }
     }
 
-    fn connections_to_unicode(connections: ((i32, i32), (i32, i32))) -> char {
-        match connections {
-            ((-1, 0), (1, 0)) => '│',
-            ((0, -1), (0, 1)) => '─',
-            ((-1, 0), (0, 1)) => '╰',
-            ((-1, 0), (0, -1)) => '╯',
-            ((1, 0), (0, 1)) => '╭',
-            ((1, 0), (0, -1)) => '╮',
-            ((2, 2), (2, 2)) => '┼',
-            _ => '·',
-        }
-    }
-
     fn connections_to_type(connections: &((i32, i32), (i32, i32))) -> Self {
         match connections {
             ((-1, 0), (1, 0)) => PipeType::VerticalPipe,
@@ -96,329 +95,954 @@ impl PipeType {
             ((-1, 0), (0, -1)) => PipeType::NorthWestPipe,
             ((1, 0), (0, 1)) => PipeType::SouthEastPipe,
             ((1, 0), (0, -1)) => PipeType::SouthWestPipe,
-            ((2, 2), (2, 2)) => PipeType::StartingPipe,
             _ => PipeType::NotAPipe,
         }
     }
+
+    // Same as connections_to_type, whatever the order of the connections
+    fn ends_to_type(first: (i32, i32), second: (i32, i32)) -> Self {
+        match PipeType::connections_to_type(&(first, second)) {
+            PipeType::NotAPipe => PipeType::connections_to_type(&(second, first)),
+            pipe => pipe,
+        }
+    }
 }
 
-struct Graph {
-    adjacency_list: HashMap<(i32, i32), ((i32, i32), (i32, i32))>,
-    rows: i32,
-    cols: i32,
+#[derive(Debug)]
+enum MazeError {
+    Io(std::io::Error),
+    Empty,
+    RaggedRow {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    MissingStart,
+    BrokenLoop {
+        from: (i32, i32),
+        to: (i32, i32),
+    },
+    NoLoop {
+        start: (i32, i32),
+    },
 }
 
-#[allow(dead_code)]
-impl Graph {
-    fn new() -> Self {
-        Graph {
-            adjacency_list: HashMap::new(),
-            rows: 0,
-            cols: 0,
+impl std::fmt::Display for MazeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MazeError::Io(err) => write!(f, "{}", err),
+            MazeError::Empty => write!(f, "the maze is empty"),
+            MazeError::RaggedRow {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: found {} cells, expected {} like the first row",
+                line, found, expected
+            ),
+            MazeError::MissingStart => write!(f, "no starting point 'S' found"),
+            MazeError::BrokenLoop { from, to } => write!(
+                f,
+                "the loop is broken, the pipe at {:?} leads to {:?} which does not connect back",
+                from, to
+            ),
+            MazeError::NoLoop { start } => {
+                write!(f, "no loop goes through the starting point at {:?}", start)
+            }
         }
     }
+}
 
-    fn add_edge(&mut self, entry: (i32, i32), connections: ((i32, i32), (i32, i32))) {
-        if let Some(existing_connections) = self.adjacency_list.get_mut(&entry) {
-            println!("warning: overwriting connections of {:?}", entry);
-            *existing_connections = connections;
-        } else {
-            self.adjacency_list.insert(entry, connections);
-            self.rows = self.rows.max(entry.0);
-            self.cols = self.cols.max(entry.1);
-        }
-    }
-
-    fn parse_point_connections(
-        &self,
-        point: &(i32, i32),
-        connections: &((i32, i32), (i32, i32)),
-    ) -> Vec<(i32, i32)> {
-        let mut conn: Vec<(i32, i32)> = Vec::new();
-        if connections == &((2, 2), (2, 2)) {
-            for dx in -1..=1 {
-                for dy in -1..=1 {
-                    if dx == 0 && dy == 0 || (dx != 0 && dy != 0) {
-                        continue;
-                    }
+impl From<std::io::Error> for MazeError {
+    fn from(err: std::io::Error) -> MazeError {
+        MazeError::Io(err)
+    }
+}
 
-                    let new_point = (point.0 + dx, point.1 + dy);
-                    let new_connections = self.adjacency_list.get(&new_point);
+// Problems found while validating the map, none of them prevents solving it
+// as long as the pipe under the start can be inferred
+enum Diagnostic {
+    MultipleStarts {
+        positions: Vec<(i32, i32)>,
+    },
+    // A chain of pipes leaving the start that never comes back to it
+    DanglingEnd {
+        pipe: (i32, i32),
+        towards: (i32, i32),
+    },
+    SeveralLoops {
+        candidates: Vec<PipeType>,
+    },
+}
 
-                    if let Some(pipe_connections) = new_connections {
-                        let new_parsed_conns =
-                            self.parse_point_connections(&new_point, pipe_connections);
-                        if new_parsed_conns.iter().any(|conn| conn == point) {
-                            conn.push(new_point);
-                        }
-                    }
-                }
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Diagnostic::MultipleStarts { positions } => write!(
+                f,
+                "found {} starting points at {:?}, using the first one",
+                positions.len(),
+                positions
+            ),
+            Diagnostic::DanglingEnd { pipe, towards } => write!(
+                f,
+                "dangling pipe end, the pipe at {:?} leads to {:?} which does not connect back",
+                pipe, towards
+            ),
+            Diagnostic::SeveralLoops { candidates } => {
+                let symbols: Vec<String> = candidates
+                    .iter()
+                    .map(|pipe| pipe.to_symbol().to_string())
+                    .collect();
+                write!(
+                    f,
+                    "{} loops go through the start, it could be any of {}, using {}",
+                    candidates.len(),
+                    symbols.join(" "),
+                    symbols[0]
+                )
             }
-        } else {
-            conn.push((point.0 + connections.0 .0, point.1 + connections.0 .1));
-            conn.push((point.0 + connections.1 .0, point.1 + connections.1 .1));
         }
-        conn
     }
+}
+
+struct Validation {
+    start_type: Option<PipeType>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AreaStrategy {
+    Scanline,
+    Shoelace,
+    FloodFill,
+}
+
+impl AreaStrategy {
+    const ALL: [AreaStrategy; 3] = [
+        AreaStrategy::Scanline,
+        AreaStrategy::Shoelace,
+        AreaStrategy::FloodFill,
+    ];
+
+    fn from_arg(arg: &str) -> Option<Self> {
+        AreaStrategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.to_string() == arg)
+    }
+}
 
-    fn dfs(&self, start: &(i32, i32), visited: &mut HashSet<(i32, i32)>) {
-        if visited.contains(&start) {
-            return;
+impl std::fmt::Display for AreaStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AreaStrategy::Scanline => write!(f, "scanline"),
+            AreaStrategy::Shoelace => write!(f, "shoelace"),
+            AreaStrategy::FloodFill => write!(f, "flood-fill"),
         }
+    }
+}
+
+// Dense row-major grid of pipes, points are (row, column)
+struct Graph {
+    cells: Vec<PipeType>,
+    rows: i32,
+    cols: i32,
+}
 
-        visited.insert(start.clone());
+impl Graph {
+    // Returns the graph and the positions of the starting points
+    fn parse<R: BufRead>(reader: R) -> Result<(Self, Vec<(i32, i32)>), MazeError> {
+        let mut cells: Vec<PipeType> = Vec::new();
+        let mut cols: usize = 0;
+        let mut rows: usize = 0;
+        let mut starting_points: Vec<(i32, i32)> = Vec::new();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            let row_start = cells.len();
+            for (j, c) in line.chars().enumerate() {
+                let pipe = PipeType::from_symbol(&c);
+                if pipe == PipeType::StartingPipe {
+                    starting_points.push((i as i32, j as i32));
+                }
+                cells.push(pipe);
+            }
 
-        if let Some(connections) = self.adjacency_list.get(start) {
-            let parsed_connections = self.parse_point_connections(&start, connections);
-            for conn in parsed_connections {
-                self.dfs(&conn, visited);
+            let found = cells.len() - row_start;
+            if i == 0 {
+                cols = found;
+            } else if found != cols {
+                return Err(MazeError::RaggedRow {
+                    line: i + 1,
+                    expected: cols,
+                    found,
+                });
             }
+            rows += 1;
+        }
+
+        if cells.is_empty() {
+            return Err(MazeError::Empty);
+        }
+        if starting_points.is_empty() {
+            return Err(MazeError::MissingStart);
+        }
+
+        let graph = Graph {
+            cells,
+            rows: rows as i32,
+            cols: cols as i32,
+        };
+        Ok((graph, starting_points))
+    }
+
+    fn index(&self, point: &(i32, i32)) -> usize {
+        (point.0 * self.cols + point.1) as usize
+    }
+
+    fn get(&self, point: &(i32, i32)) -> Option<PipeType> {
+        if point.0 < 0 || point.1 < 0 || point.0 >= self.rows || point.1 >= self.cols {
+            return None;
+        }
+        Some(self.cells[self.index(point)])
+    }
+
+    // Both ends of a regular pipe, None for ground, the start and outside
+    fn pipe_ends(&self, point: &(i32, i32)) -> Option<[(i32, i32); 2]> {
+        let (first, second) = self.get(point)?.to_connections()?;
+        Some([
+            (point.0 + first.0, point.1 + first.1),
+            (point.0 + second.0, point.1 + second.1),
+        ])
+    }
+
+    fn parse_point_connections(&self, point: &(i32, i32)) -> Vec<(i32, i32)> {
+        if self.get(point) != Some(PipeType::StartingPipe) {
+            return self
+                .pipe_ends(point)
+                .map_or(Vec::new(), |ends| ends.to_vec());
         }
+
+        // The start connects to every neighbour that connects back to it
+        [(-1, 0), (0, -1), (0, 1), (1, 0)]
+            .iter()
+            .map(|(dx, dy)| (point.0 + dx, point.1 + dy))
+            .filter(|neighbour| {
+                self.pipe_ends(neighbour)
+                    .is_some_and(|ends| ends.contains(point))
+            })
+            .collect()
     }
 
-    fn extract_loop(&self, start: &(i32, i32)) -> HashSet<(i32, i32)> {
-        let mut visited: HashSet<(i32, i32)> = HashSet::new();
-        self.dfs(start, &mut visited);
-        visited
+    // Follows the pipes from the start through `first` until coming back to
+    // the start, returning the points in order
+    fn walk(&self, start: &(i32, i32), first: (i32, i32)) -> Result<Vec<(i32, i32)>, MazeError> {
+        let mut circuit: Vec<(i32, i32)> = vec![*start];
+        let mut previous = *start;
+        let mut current = first;
+
+        while current != *start {
+            let ends = match self.pipe_ends(&current) {
+                Some(ends) if ends.contains(&previous) => ends,
+                _ => {
+                    return Err(MazeError::BrokenLoop {
+                        from: previous,
+                        to: current,
+                    })
+                }
+            };
+            circuit.push(current);
+            let next = if ends[0] == previous {
+                ends[1]
+            } else {
+                ends[0]
+            };
+            previous = current;
+            current = next;
+        }
+
+        Ok(circuit)
     }
 
-    fn bfs(
-        &self,
-        start: &(i32, i32),
-        visited: &mut HashSet<(i32, i32)>,
-    ) -> HashMap<(i32, i32), u32> {
-        let mut q: LinkedList<(i32, i32)> = LinkedList::new();
-        q.push_back(start.clone());
+    // Walks the loop from the start, once its pipe has been inferred
+    fn extract_loop(&self, start: &(i32, i32)) -> Result<Vec<(i32, i32)>, MazeError> {
+        match self.pipe_ends(start) {
+            Some(ends) => self.walk(start, ends[0]),
+            None => Err(MazeError::NoLoop { start: *start }),
+        }
+    }
 
-        let mut distances: HashMap<(i32, i32), u32> = HashMap::new();
-        distances.insert(start.clone(), 0);
+    // Infers the pipe under the first start by following every chain of
+    // pipes that leaves it, the ones that come back form candidate loops
+    fn validate(&self, starts: &[(i32, i32)]) -> Validation {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        if starts.len() > 1 {
+            diagnostics.push(Diagnostic::MultipleStarts {
+                positions: starts.to_vec(),
+            });
+        }
 
-        while let Some(current) = q.pop_front() {
-            if let Some(connections) = self.adjacency_list.get(&current) {
-                let parsed_connections = self.parse_point_connections(&current, connections);
-                for conn in parsed_connections {
-                    if visited.contains(&conn) {
+        let start = starts[0];
+        let relative = |point: (i32, i32)| (point.0 - start.0, point.1 - start.1);
+        let mut candidates: Vec<PipeType> = Vec::new();
+        let neighbours = self.parse_point_connections(&start);
+        for (i, &first) in neighbours.iter().enumerate() {
+            match self.walk(&start, first) {
+                Ok(circuit) => {
+                    // Each loop is found once from each of its two ends
+                    let last = circuit[circuit.len() - 1];
+                    if neighbours[..i].contains(&last) {
                         continue;
                     }
-                    visited.insert(conn.clone());
-                    let distance = distances[&current] + 1;
-                    distances.entry(conn.clone()).or_insert(distance);
-                    q.push_back(conn.clone());
+                    candidates.push(PipeType::ends_to_type(relative(first), relative(last)));
+                }
+                Err(MazeError::BrokenLoop { from, to }) => {
+                    diagnostics.push(Diagnostic::DanglingEnd {
+                        pipe: from,
+                        towards: to,
+                    });
                 }
+                Err(_) => {}
             }
         }
 
-        distances
-    }
+        if candidates.len() > 1 {
+            diagnostics.push(Diagnostic::SeveralLoops {
+                candidates: candidates.clone(),
+            });
+        }
 
-    fn farthest_from(&self, start: &(i32, i32)) -> u32 {
-        let mut visited: HashSet<(i32, i32)> = HashSet::new();
-        let distances = self.bfs(start, &mut visited);
-        // self.print_maze_with_distances(&distances);
-        *distances.values().max().unwrap()
+        Validation {
+            start_type: candidates.first().copied(),
+            diagnostics,
+        }
     }
 
-    fn count_inner_points(&self, circuit: &HashSet<(i32, i32)>) -> Vec<(i32, i32)> {
-        let mut inner: Vec<(i32, i32)> = Vec::new();
-        let mut last_corner_pipe = PipeType::NotAPipe;
-
-        for i in 0..=self.rows {
-            let mut intersections: u32 = 0;
-            for j in 0..=self.cols {
-                let point = (i, j);
-                let node_conns = self.adjacency_list.get(&point).unwrap();
+    fn set_pipe(&mut self, point: &(i32, i32), pipe: PipeType) {
+        let index = self.index(point);
+        self.cells[index] = pipe;
+    }
 
-                let point_in_circuit = circuit.contains(&point);
+    // The map in the puzzle format
+    fn to_map(&self) -> String {
+        let mut map = String::with_capacity(self.cells.len() + self.rows as usize);
+        for row in self.cells.chunks(self.cols as usize) {
+            map.extend(row.iter().map(|pipe| pipe.to_symbol()));
+            map.push('\n');
+        }
+        map
+    }
 
-                let mut current_pipe_type = if !point_in_circuit {
-                    PipeType::NotAPipe
-                } else {
-                    PipeType::connections_to_type(node_conns)
-                };
+    fn loop_mask(&self, circuit: &[(i32, i32)]) -> Vec<bool> {
+        let mut mask = vec![false; self.cells.len()];
+        for point in circuit {
+            mask[self.index(point)] = true;
+        }
+        mask
+    }
 
-                // Update the type of the starting point
-                if current_pipe_type == PipeType::StartingPipe {
-                    let actual_pipe = self.parse_point_connections(&point, node_conns);
+    // Every point of the loop is reached from both directions, the farthest
+    // one is halfway around
+    fn farthest_from(circuit: &[(i32, i32)]) -> usize {
+        circuit.len() / 2
+    }
 
-                    let mut first = actual_pipe.last().unwrap().clone();
-                    first.0 = first.0 - point.0;
-                    first.1 = first.1 - point.1;
+    fn count_inner_points(&self, circuit: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        let mut inner: Vec<(i32, i32)> = Vec::new();
+        let in_circuit = self.loop_mask(circuit);
 
-                    let mut second = actual_pipe.first().unwrap().clone();
-                    second.0 = second.0 - point.0;
-                    second.1 = second.1 - point.1;
+        for i in 0..self.rows {
+            let mut intersections: u32 = 0;
+            let mut last_corner_pipe = PipeType::NotAPipe;
+            for j in 0..self.cols {
+                let point = (i, j);
+                let index = self.index(&point);
 
-                    current_pipe_type = PipeType::connections_to_type(&(first, second));
+                if !in_circuit[index] {
+                    if !intersections.is_multiple_of(2) {
+                        inner.push(point);
+                    }
+                    continue;
                 }
 
-                if current_pipe_type != PipeType::HorizontalPipe && point_in_circuit {
-                    if !((last_corner_pipe == PipeType::SouthEastPipe
+                let current_pipe_type = self.cells[index];
+
+                if current_pipe_type != PipeType::HorizontalPipe
+                    && !((last_corner_pipe == PipeType::SouthEastPipe
                         && current_pipe_type == PipeType::NorthWestPipe)
                         || (last_corner_pipe == PipeType::NorthEastPipe
                             && current_pipe_type == PipeType::SouthWestPipe))
-                    {
-                        intersections += 1;
-                    }
+                {
+                    intersections += 1;
                 }
 
-                last_corner_pipe = if current_pipe_type == PipeType::NorthEastPipe
+                if current_pipe_type == PipeType::NorthEastPipe
                     || current_pipe_type == PipeType::SouthEastPipe
                 {
-                    current_pipe_type
-                } else {
-                    last_corner_pipe
-                };
-
-                if intersections % 2 != 0 {
-                    if (node_conns.0 == (0, 0) && node_conns.1 == (0, 0)) || !point_in_circuit {
-                        inner.push(point);
-                    }
+                    last_corner_pipe = current_pipe_type;
                 }
             }
         }
         inner
     }
 
-    // Draw functions
+    // Shoelace formula gives the area enclosed by the centres of the loop
+    // tiles, Pick's theorem turns it into the number of points strictly
+    // inside: A = I + B / 2 - 1, with every loop tile being a boundary point
+    fn count_inner_shoelace(circuit: &[(i32, i32)]) -> usize {
+        let twice_area: i64 = circuit
+            .iter()
+            .zip(circuit.iter().cycle().skip(1))
+            .map(|(a, b)| a.0 as i64 * b.1 as i64 - b.0 as i64 * a.1 as i64)
+            .sum();
+        let boundary = circuit.len() as i64;
+        ((twice_area.abs() - boundary) / 2 + 1) as usize
+    }
 
-    fn draw(&self) {
-        let max_x = self
-            .adjacency_list
-            .keys()
-            .map(|&(x, _)| x)
-            .max()
-            .unwrap_or(0);
-
-        let max_y = self
-            .adjacency_list
-            .keys()
-            .map(|&(_, y)| y)
-            .max()
-            .unwrap_or(0);
-
-        for i in 0..=max_x {
-            for j in 0..=max_y {
-                let point = (i, j);
-                if let Some(connections) = self.adjacency_list.get(&point) {
-                    let symbol = PipeType::connections_to_unicode(*connections);
-                    print!("{}", symbol);
-                } else {
-                    print!("   ");
+    // Flood fill from the outside on a grid with twice the resolution, so
+    // that the gaps between two adjacent pipes become cells of their own.
+    // Tile (i, j) sits at (2i + 1, 2j + 1) and a border of gaps surrounds
+    // the whole maze.
+    fn count_inner_flood_fill(&self, circuit: &[(i32, i32)]) -> usize {
+        let rows = 2 * self.rows as usize + 1;
+        let cols = 2 * self.cols as usize + 1;
+        let at = |point: (i32, i32)| (2 * point.0 as usize + 1) * cols + 2 * point.1 as usize + 1;
+
+        let mut blocked = vec![false; rows * cols];
+        for (a, b) in circuit.iter().zip(circuit.iter().cycle().skip(1)) {
+            blocked[at(*a)] = true;
+            // Midpoint between two consecutive loop tiles
+            blocked[(at(*a) + at(*b)) / 2] = true;
+        }
+
+        let mut outside = vec![false; rows * cols];
+        let mut stack: Vec<usize> = vec![0];
+        outside[0] = true;
+        while let Some(index) = stack.pop() {
+            let (i, j) = (index / cols, index % cols);
+            let mut neighbours: Vec<usize> = Vec::with_capacity(4);
+            if i > 0 {
+                neighbours.push(index - cols);
+            }
+            if i + 1 < rows {
+                neighbours.push(index + cols);
+            }
+            if j > 0 {
+                neighbours.push(index - 1);
+            }
+            if j + 1 < cols {
+                neighbours.push(index + 1);
+            }
+            for next in neighbours {
+                if !blocked[next] && !outside[next] {
+                    outside[next] = true;
+                    stack.push(next);
                 }
             }
-            println!();
         }
+
+        (0..self.rows)
+            .flat_map(|i| (0..self.cols).map(move |j| (i, j)))
+            .filter(|&point| !blocked[at(point)] && !outside[at(point)])
+            .count()
     }
 
-    fn draw_filter(&self, filter: &HashSet<(i32, i32)>) {
-        let max_x = self
-            .adjacency_list
-            .keys()
-            .map(|&(x, _)| x)
-            .max()
-            .unwrap_or(0);
+    fn count_inner(&self, circuit: &[(i32, i32)], strategy: AreaStrategy) -> usize {
+        match strategy {
+            AreaStrategy::Scanline => self.count_inner_points(circuit).len(),
+            AreaStrategy::Shoelace => Graph::count_inner_shoelace(circuit),
+            AreaStrategy::FloodFill => self.count_inner_flood_fill(circuit),
+        }
+    }
+}
 
-        let max_y = self
-            .adjacency_list
-            .keys()
-            .map(|&(_, y)| y)
-            .max()
-            .unwrap_or(0);
+#[allow(dead_code)]
+impl Graph {
+    // Draw functions
 
-        for i in 0..=max_x {
-            for j in 0..=max_y {
-                let point = (i, j);
-                if filter.contains(&point) {
-                    if let Some(connections) = self.adjacency_list.get(&point) {
-                        let symbol = PipeType::connections_to_unicode(*connections);
-                        print!("{}", symbol);
+    fn draw(&self) {
+        for row in self.cells.chunks(self.cols as usize) {
+            let line: String = row.iter().map(|pipe| pipe.to_unicode()).collect();
+            println!("{}", line);
+        }
+    }
+
+    fn draw_filter(&self, filter: &[bool]) {
+        for (row, row_filter) in self
+            .cells
+            .chunks(self.cols as usize)
+            .zip(filter.chunks(self.cols as usize))
+        {
+            let line: String = row
+                .iter()
+                .zip(row_filter)
+                .map(|(pipe, &keep)| {
+                    if keep {
+                        pipe.to_unicode()
                     } else {
-                        print!("   ");
+                        PipeType::NotAPipe.to_unicode()
                     }
-                } else {
-                    print!("{}", PipeType::to_unicode(&PipeType::NotAPipe));
+                })
+                .collect();
+            println!("{}", line);
+        }
+    }
+
+    fn draw_inner_points(&self, inner: &[(i32, i32)], filter: &[bool]) {
+        let mut is_inner = vec![false; self.cells.len()];
+        for point in inner {
+            is_inner[self.index(point)] = true;
+        }
+
+        for (index, pipe) in self.cells.iter().enumerate() {
+            if is_inner[index] {
+                print!("I");
+            } else if filter[index] {
+                print!("{}", pipe.to_unicode());
+            } else {
+                print!("{}", PipeType::NotAPipe.to_unicode());
+            }
+            if (index + 1) % self.cols as usize == 0 {
+                println!();
+            }
+        }
+    }
+}
+
+// Colours of the renders, shared by the SVG and PNG exports
+const OUTSIDE_COLOR: [u8; 3] = [0xe8, 0xe8, 0xe8];
+const INSIDE_COLOR: [u8; 3] = [0x9b, 0xe3, 0x9b];
+const LOOP_BACKGROUND_COLOR: [u8; 3] = [0xff, 0xff, 0xff];
+const JUNK_COLOR: [u8; 3] = [0xa0, 0xa0, 0xa0];
+const LOOP_COLOR: [u8; 3] = [0x1f, 0x4e, 0x9c];
+const START_COLOR: [u8; 3] = [0xd6, 0x27, 0x28];
+const FARTHEST_COLOR: [u8; 3] = [0xff, 0x8c, 0x00];
+
+fn hex_color(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+// The maze split into the layers of the renders: tiles inside and outside of
+// the main loop, the main loop itself and the junk pipes not part of it
+struct Scene<'a> {
+    graph: &'a Graph,
+    circuit: &'a [(i32, i32)],
+    in_circuit: Vec<bool>,
+    inside: Vec<bool>,
+}
+
+impl<'a> Scene<'a> {
+    const SVG_TILE: i32 = 10;
+    const PNG_TILE: usize = 5;
+
+    fn new(graph: &'a Graph, circuit: &'a [(i32, i32)]) -> Self {
+        let mut inside = vec![false; graph.cells.len()];
+        for point in graph.count_inner_points(circuit) {
+            inside[graph.index(&point)] = true;
+        }
+        Scene {
+            graph,
+            circuit,
+            in_circuit: graph.loop_mask(circuit),
+            inside,
+        }
+    }
+
+    fn start(&self) -> (i32, i32) {
+        self.circuit[0]
+    }
+
+    fn farthest(&self) -> (i32, i32) {
+        self.circuit[Graph::farthest_from(self.circuit)]
+    }
+
+    fn background(&self, index: usize) -> [u8; 3] {
+        if self.in_circuit[index] {
+            LOOP_BACKGROUND_COLOR
+        } else if self.inside[index] {
+            INSIDE_COLOR
+        } else {
+            OUTSIDE_COLOR
+        }
+    }
+
+    // Rectangles covering the tiles of one layer, merging runs on each row
+    fn svg_rects(&self, layer: &[bool]) -> String {
+        let tile = Scene::SVG_TILE;
+        let mut rects = String::new();
+        for (i, row) in layer.chunks(self.graph.cols as usize).enumerate() {
+            let mut j = 0;
+            while j < row.len() {
+                if !row[j] {
+                    j += 1;
+                    continue;
+                }
+                let run_start = j;
+                while j < row.len() && row[j] {
+                    j += 1;
                 }
+                rects.push_str(&format!(
+                    "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+                    run_start as i32 * tile,
+                    i as i32 * tile,
+                    (j - run_start) as i32 * tile,
+                    tile
+                ));
             }
-            println!();
         }
+        rects
+    }
+
+    fn svg_center(point: &(i32, i32)) -> (i32, i32) {
+        let tile = Scene::SVG_TILE;
+        (point.1 * tile + tile / 2, point.0 * tile + tile / 2)
     }
 
-    fn draw_inner_points(&self, inner: &Vec<(i32, i32)>, filter: &HashSet<(i32, i32)>) {
-        let max_x = self
-            .adjacency_list
-            .keys()
-            .map(|&(x, _)| x)
-            .max()
-            .unwrap_or(0);
+    fn svg_marker(&self, point: &(i32, i32), color: [u8; 3], title: &str) -> String {
+        let (x, y) = Scene::svg_center(point);
+        format!(
+            "    <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"><title>{} {:?}</title></circle>\n",
+            x,
+            y,
+            Scene::SVG_TILE / 3,
+            hex_color(color),
+            title,
+            point
+        )
+    }
 
-        let max_y = self
-            .adjacency_list
-            .keys()
-            .map(|&(_, y)| y)
-            .max()
-            .unwrap_or(0);
+    fn to_svg(&self) -> String {
+        let tile = Scene::SVG_TILE;
+        let width = self.graph.cols * tile;
+        let height = self.graph.rows * tile;
+        let outside: Vec<bool> = (0..self.graph.cells.len())
+            .map(|index| !self.in_circuit[index] && !self.inside[index])
+            .collect();
 
-        for i in 0..=max_x {
-            for j in 0..=max_y {
-                let point = (i, j);
-                if let Some(connections) = self.adjacency_list.get(&point) {
-                    let symbol = PipeType::connections_to_unicode(*connections);
-                    if inner.contains(&point) {
-                        print!("I");
-                    } else {
-                        if filter.contains(&point) {
-                            print!("{}", symbol);
-                        } else {
-                            print!("{}", PipeType::to_unicode(&PipeType::NotAPipe));
-                        }
-                    }
+        // Every junk pipe goes from the middle of one side of its tile to
+        // the middle of the other through the centre
+        let mut junk = String::new();
+        for (index, pipe) in self.graph.cells.iter().enumerate() {
+            if self.in_circuit[index] {
+                continue;
+            }
+            if let Some((first, second)) = pipe.to_connections() {
+                let point = (
+                    index as i32 / self.graph.cols,
+                    index as i32 % self.graph.cols,
+                );
+                let (x, y) = Scene::svg_center(&point);
+                junk.push_str(&format!(
+                    "M{} {}L{} {}L{} {}",
+                    x + first.1 * tile / 2,
+                    y + first.0 * tile / 2,
+                    x,
+                    y,
+                    x + second.1 * tile / 2,
+                    y + second.0 * tile / 2
+                ));
+            }
+        }
+
+        let main_loop: Vec<String> = self
+            .circuit
+            .iter()
+            .map(|point| {
+                let (x, y) = Scene::svg_center(point);
+                format!("{} {}", x, y)
+            })
+            .collect();
+
+        let mut svg = String::new();
+        svg.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            width, height, width, height
+        ));
+        svg.push_str(&format!(
+            "  <rect id=\"background\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            width,
+            height,
+            hex_color(LOOP_BACKGROUND_COLOR)
+        ));
+        svg.push_str(&format!(
+            "  <g id=\"outside\" fill=\"{}\">\n{}  </g>\n",
+            hex_color(OUTSIDE_COLOR),
+            self.svg_rects(&outside)
+        ));
+        svg.push_str(&format!(
+            "  <g id=\"inside\" fill=\"{}\">\n{}  </g>\n",
+            hex_color(INSIDE_COLOR),
+            self.svg_rects(&self.inside)
+        ));
+        svg.push_str(&format!(
+            "  <g id=\"junk\" stroke=\"{}\" stroke-width=\"{}\" fill=\"none\">\n    <path d=\"{}\"/>\n  </g>\n",
+            hex_color(JUNK_COLOR),
+            tile / 5,
+            junk
+        ));
+        svg.push_str(&format!(
+            "  <g id=\"loop\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\" fill=\"none\">\n    <path d=\"M{}Z\"/>\n  </g>\n",
+            hex_color(LOOP_COLOR),
+            tile * 3 / 10,
+            main_loop.join("L")
+        ));
+        svg.push_str("  <g id=\"markers\">\n");
+        svg.push_str(&self.svg_marker(&self.start(), START_COLOR, "start"));
+        svg.push_str(&self.svg_marker(&self.farthest(), FARTHEST_COLOR, "farthest point"));
+        svg.push_str("  </g>\n</svg>\n");
+        svg
+    }
+
+    // Every tile is PNG_TILE pixels wide, pipes are one pixel thick lines
+    // from the centre of the tile to the sides they connect
+    fn to_png(&self) -> Vec<u8> {
+        let tile = Scene::PNG_TILE;
+        let half = tile / 2;
+        let width = self.graph.cols as usize * tile;
+        let height = self.graph.rows as usize * tile;
+        let mut pixels = vec![0u8; width * height * 3];
+        let mut paint = |x: usize, y: usize, color: [u8; 3]| {
+            let offset = (y * width + x) * 3;
+            pixels[offset..offset + 3].copy_from_slice(&color);
+        };
+
+        for (index, pipe) in self.graph.cells.iter().enumerate() {
+            let (x0, y0) = (
+                index % self.graph.cols as usize * tile,
+                index / self.graph.cols as usize * tile,
+            );
+            let background = self.background(index);
+            for y in y0..y0 + tile {
+                for x in x0..x0 + tile {
+                    paint(x, y, background);
+                }
+            }
+
+            if let Some((first, second)) = pipe.to_connections() {
+                let color = if self.in_circuit[index] {
+                    LOOP_COLOR
                 } else {
-                    print!("   ");
+                    JUNK_COLOR
+                };
+                paint(x0 + half, y0 + half, color);
+                for (dy, dx) in [first, second] {
+                    for step in 1..=half as i32 {
+                        let x = (x0 + half) as i32 + dx * step;
+                        let y = (y0 + half) as i32 + dy * step;
+                        paint(x as usize, y as usize, color);
+                    }
+                }
+            }
+        }
+
+        for (point, color) in [
+            (self.start(), START_COLOR),
+            (self.farthest(), FARTHEST_COLOR),
+        ] {
+            let (x0, y0) = (point.1 as usize * tile, point.0 as usize * tile);
+            for y in y0 + half - 1..=y0 + half + 1 {
+                for x in x0 + half - 1..=x0 + half + 1 {
+                    paint(x, y, color);
                 }
             }
-            println!();
         }
+
+        encode_png(width as u32, height as u32, &pixels)
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffff_ffff;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b): (u32, u32) = (1, 0);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// 8-bit RGB image, compressed with stored (uncompressed) deflate blocks so
+// no actual compressor is needed
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut scanlines: Vec<u8> = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width as usize * 3) {
+        // Filter type None
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
     }
+
+    let mut zlib: Vec<u8> = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = scanlines.chunks(u16::MAX as usize).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&scanlines).to_be_bytes());
+
+    let mut header: Vec<u8> = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, colour type RGB, default compression, filter, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png: Vec<u8> = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day10 <filename>");
+        println!(
+            "Usage: ./day10 <filename> [--area <scanline | shoelace | flood-fill>] [--cross-check] [--validate] [--rewrite <output>] [--svg <output>] [--png <output>]"
+        );
         std::process::exit(1);
     }
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
-    let mut graph = Graph::new();
-    let mut starting_point: (i32, i32) = (0, 0);
-    for (i, line) in reader.lines().enumerate() {
-        for (j, c) in line?.chars().enumerate() {
-            let point = (i as i32, j as i32);
-            let pipe = PipeType::from_symbol(&c);
-            let connections = pipe.to_connections();
-            starting_point = if pipe == PipeType::StartingPipe {
-                point
-            } else {
-                starting_point
-            };
-            graph.add_edge(point, connections);
+    let (mut graph, starting_points) = match Graph::parse(reader) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let starting_point = starting_points[0];
+
+    let validation = graph.validate(&starting_points);
+    for diagnostic in &validation.diagnostics {
+        eprintln!("warning: {}", diagnostic);
+    }
+    let start_type = match validation.start_type {
+        Some(pipe) => pipe,
+        None => {
+            eprintln!(
+                "Error: {}",
+                MazeError::NoLoop {
+                    start: starting_point
+                }
+            );
+            std::process::exit(1);
+        }
+    };
+    graph.set_pipe(&starting_point, start_type);
+
+    if args.iter().any(|arg| arg == "--validate") {
+        println!(
+            "Start at {:?} is a {} '{}', {} diagnostics",
+            starting_point,
+            start_type,
+            start_type.to_symbol(),
+            validation.diagnostics.len()
+        );
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--rewrite") {
+        let Some(output) = args.get(i + 1) else {
+            eprintln!("Error: --rewrite expects an output file");
+            std::process::exit(1);
+        };
+        std::fs::write(output, graph.to_map())?;
+    }
+
+    let extracted_loop = match graph.extract_loop(&starting_point) {
+        Ok(circuit) => circuit,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let strategy = match args.iter().position(|arg| arg == "--area") {
+        Some(i) => match args.get(i + 1).and_then(|arg| AreaStrategy::from_arg(arg)) {
+            Some(strategy) => strategy,
+            None => {
+                eprintln!("Error: --area expects one of scanline, shoelace or flood-fill");
+                std::process::exit(1);
+            }
+        },
+        None => AreaStrategy::Scanline,
+    };
+
+    if args.iter().any(|arg| arg == "--cross-check") {
+        let counts: Vec<(AreaStrategy, usize)> = AreaStrategy::ALL
+            .iter()
+            .map(|&strategy| (strategy, graph.count_inner(&extracted_loop, strategy)))
+            .collect();
+        for (strategy, count) in &counts {
+            println!("Inner points with {}: {}", strategy, count);
+        }
+        if counts.iter().any(|(_, count)| *count != counts[0].1) {
+            eprintln!("warning: the enclosed area strategies disagree");
         }
     }
 
-    let extracted_loop = graph.extract_loop(&starting_point);
-    let inner_points = graph.count_inner_points(&extracted_loop);
+    let output_of = |flag: &str| {
+        let i = args.iter().position(|arg| arg == flag)?;
+        match args.get(i + 1) {
+            Some(output) => Some(output.clone()),
+            None => {
+                eprintln!("Error: {} expects an output file", flag);
+                std::process::exit(1);
+            }
+        }
+    };
+    let svg_output = output_of("--svg");
+    let png_output = output_of("--png");
+    if svg_output.is_some() || png_output.is_some() {
+        let scene = Scene::new(&graph, &extracted_loop);
+        if let Some(output) = svg_output {
+            std::fs::write(output, scene.to_svg())?;
+        }
+        if let Some(output) = png_output {
+            std::fs::write(output, scene.to_png())?;
+        }
+    }
 
+    // let inner_points = graph.count_inner_points(&extracted_loop);
     // println!("\n---------------STARTING---------------\n");
     // graph.draw();
     // println!("\n-----------------LOOP-----------------\n");
-    // graph.draw_filter(&extracted_loop);
+    // graph.draw_filter(&graph.loop_mask(&extracted_loop));
     // println!("\n-------------INNER-POINTS-------------\n");
-    // graph.draw_inner_points(&inner_points, &extracted_loop);
+    // graph.draw_inner_points(&inner_points, &graph.loop_mask(&extracted_loop));
     // println!("\n----------------RESULT----------------\n");
 
-    let result1 = graph.farthest_from(&starting_point);
-    let result2 = inner_points.len();
+    let result1 = Graph::farthest_from(&extracted_loop);
+    let result2 = graph.count_inner(&extracted_loop, strategy);
 
     println!("Result for part 1: {}", result1);
     println!("Result for part 2: {}", result2);
@@ -1,133 +1,290 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
 #[derive(Clone, Copy)]
+// u128 since expanded positions go past u64 with factors near u64::MAX
 struct Point {
-    y: u64,
-    x: u64,
+    y: u128,
+    x: u128,
 }
 
 impl Point {
-    fn from(x: u64, y: u64) -> Self {
+    fn from(x: u128, y: u128) -> Self {
         Point { x, y }
     }
 }
 
-#[derive(Clone, Copy)]
-struct Galaxy {
-    id: u64,
-    position: Point,
+// How much every empty line along one axis grows, `factor` for all of them
+// unless overridden for a given line. Overrides of lines holding galaxies
+// are ignored.
+#[derive(Clone)]
+struct AxisExpansion {
+    factor: u64,
+    overrides: BTreeMap<u64, u64>,
 }
 
-impl Galaxy {
-    fn from(id: u64, position: Point) -> Self {
-        Galaxy { id, position }
+impl AxisExpansion {
+    fn uniform(factor: u64) -> Self {
+        AxisExpansion {
+            factor,
+            overrides: BTreeMap::new(),
+        }
     }
 }
 
-struct Universe {
-    galaxies: Vec<Galaxy>,
-    galaxies_inflated: Vec<Galaxy>,
-    rows_occupied: HashSet<u64>,
-    cols_occupied: HashSet<u64>,
-    pairs: Vec<(u64, u64)>,
-    rows: u64,
-    cols: u64,
+#[derive(Clone)]
+struct Expansion {
+    rows: AxisExpansion,
+    cols: AxisExpansion,
 }
 
-impl Universe {
-    fn new() -> Self {
-        Universe {
-            galaxies: Vec::new(),
-            galaxies_inflated: Vec::new(),
-            rows_occupied: HashSet::new(),
-            cols_occupied: HashSet::new(),
-            pairs: Vec::new(),
-            rows: 0,
-            cols: 0,
+impl Expansion {
+    fn uniform(factor: u64) -> Self {
+        Expansion {
+            rows: AxisExpansion::uniform(factor),
+            cols: AxisExpansion::uniform(factor),
         }
     }
 
-    fn push_galaxy(&mut self, galaxy: Galaxy) {
-        self.cols = self.cols.max(galaxy.position.x);
-        self.rows = self.rows.max(galaxy.position.y);
-
-        self.rows_occupied.insert(galaxy.position.y);
-        self.cols_occupied.insert(galaxy.position.x);
+    // One setting per line, lines are numbered from 0 like the positions:
+    //   rows <factor>
+    //   cols <factor>
+    //   row <line> <factor>
+    //   col <line> <factor>
+    // Blank lines and lines starting with '#' are skipped.
+    fn apply_spec<R: BufRead>(&mut self, reader: R) -> Result<(), String> {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|err| err.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
 
-        for stored_galaxy in &self.galaxies {
-            self.pairs.push((galaxy.id, stored_galaxy.id));
+            let invalid = || format!("spec line {}: invalid setting '{}'", i + 1, line);
+            let values: Vec<u64> = line
+                .split_whitespace()
+                .skip(1)
+                .map(|value| value.parse().map_err(|_| invalid()))
+                .collect::<Result<_, _>>()?;
+            match (line.split_whitespace().next(), values.as_slice()) {
+                (Some("rows"), &[factor]) => self.rows.factor = factor,
+                (Some("cols"), &[factor]) => self.cols.factor = factor,
+                (Some("row"), &[row, factor]) => {
+                    self.rows.overrides.insert(row, factor);
+                }
+                (Some("col"), &[col, factor]) => {
+                    self.cols.overrides.insert(col, factor);
+                }
+                _ => return Err(invalid()),
+            }
         }
-
-        self.galaxies.push(galaxy);
-        self.galaxies_inflated.push(galaxy);
+        Ok(())
     }
+}
 
-    fn inflate_row(&mut self, row: u64, step: u64) {
-        for (galaxy, original) in self.galaxies_inflated.iter_mut().zip(&self.galaxies) {
-            if original.position.y > row {
-                galaxy.position.y += step
-            };
-            self.rows = self.rows.max(galaxy.position.y);
+// Coordinates along one axis once every empty line is replaced by as many
+// lines as its factor. The empty lines before a coordinate are the ones not
+// occupied by any galaxy, found by ranking it among the sorted occupied
+// lines, overridden lines then add their difference with the default factor.
+fn expand_axis(coords: &[u128], expansion: &AxisExpansion) -> Vec<u128> {
+    let mut occupied: Vec<u128> = coords.to_vec();
+    occupied.sort_unstable();
+    occupied.dedup();
+
+    // Cumulative adjustment up to and including each overridden empty line
+    let mut adjustments: Vec<(u128, i128)> = Vec::new();
+    let mut adjustment: i128 = 0;
+    for (&line, &factor) in &expansion.overrides {
+        let line = line as u128;
+        if occupied.binary_search(&line).is_ok() {
+            continue;
         }
+        adjustment += factor as i128 - expansion.factor as i128;
+        adjustments.push((line, adjustment));
     }
 
-    fn inflate_col(&mut self, col: u64, step: u64) {
-        for (galaxy, original) in self.galaxies_inflated.iter_mut().zip(&self.galaxies) {
-            if original.position.x > col {
-                galaxy.position.x += step
+    coords
+        .iter()
+        .map(|&coord| {
+            let empty_before = coord - occupied.partition_point(|&line| line < coord) as u128;
+            let overridden = adjustments.partition_point(|&(line, _)| line < coord);
+            let adjustment = match overridden {
+                0 => 0,
+                i => adjustments[i - 1].1,
             };
-            self.cols = self.cols.max(galaxy.position.x);
-        }
+            // Never negative, overridden lines still count for their factor
+            ((coord - empty_before) as i128
+                + empty_before as i128 * expansion.factor as i128
+                + adjustment) as u128
+        })
+        .collect()
+}
+
+// Sum of the distances between every pair of coordinates along one axis.
+// Once sorted, each coordinate is the far end of a pair with every one
+// before it, which adds up to coord * i minus the sum of those before.
+fn sum_of_distances_along(mut coords: Vec<u128>) -> u128 {
+    coords.sort_unstable();
+
+    let mut before: u128 = 0;
+    let mut total: u128 = 0;
+    for (i, &coord) in coords.iter().enumerate() {
+        total += coord * i as u128 - before;
+        before += coord;
     }
+    total
+}
+
+#[derive(Clone, Copy)]
+enum Metric {
+    Manhattan,
+    Euclidean,
+    Chebyshev,
+}
 
-    fn inflate(&mut self, step: u64) {
-        if step <= 0 {
-            println!("Warning: Could not handle step equal or below 0");
-            return;
+impl Metric {
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "manhattan" => Some(Metric::Manhattan),
+            "euclidean" => Some(Metric::Euclidean),
+            "chebyshev" => Some(Metric::Chebyshev),
+            _ => None,
         }
-        let step = step - 1;
-        for i in 0..=self.rows {
-            if !self.rows_occupied.contains(&i) {
-                self.inflate_row(i, step);
-            }
+    }
+
+    fn distance(&self, a: &Point, b: &Point) -> f64 {
+        let dx = a.x.abs_diff(b.x) as f64;
+        let dy = a.y.abs_diff(b.y) as f64;
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Euclidean => dx.hypot(dy),
+            Metric::Chebyshev => dx.max(dy),
         }
-        for j in 0..=self.cols {
-            if !self.cols_occupied.contains(&j) {
-                self.inflate_col(j, step);
-            }
+    }
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Metric::Manhattan => write!(f, "manhattan"),
+            Metric::Euclidean => write!(f, "euclidean"),
+            Metric::Chebyshev => write!(f, "chebyshev"),
         }
     }
+}
 
-    fn process_sum_of_distances(&self) -> u64 {
-        let mut id_map: HashMap<u64, &Point> = HashMap::new();
-        for galaxy in &self.galaxies_inflated {
-            id_map.insert(galaxy.id, &galaxy.position);
+// Galaxies in reading order, galaxy n being galaxies[n - 1]
+struct Universe {
+    galaxies: Vec<Point>,
+}
+
+impl Universe {
+    fn new() -> Self {
+        Universe {
+            galaxies: Vec::new(),
         }
+    }
+
+    fn push_galaxy(&mut self, galaxy: Point) {
+        self.galaxies.push(galaxy);
+    }
+
+    // Positions of the galaxies once the empty rows and columns expanded
+    fn inflate(&self, expansion: &Expansion) -> Vec<Point> {
+        let xs: Vec<u128> = self.galaxies.iter().map(|g| g.x).collect();
+        let ys: Vec<u128> = self.galaxies.iter().map(|g| g.y).collect();
+
+        expand_axis(&xs, &expansion.cols)
+            .into_iter()
+            .zip(expand_axis(&ys, &expansion.rows))
+            .map(|(x, y)| Point::from(x, y))
+            .collect()
+    }
 
-        let mut total = 0;
-        for pair in &self.pairs {
-            let p1: &Point = id_map.get(&pair.0).unwrap();
-            let p2: &Point = id_map.get(&pair.1).unwrap();
-            let diff = (p1.x.max(p2.x) - p1.x.min(p2.x)) + (p1.y.max(p2.y) - p1.y.min(p2.y));
-            total += diff;
+    // Manhattan distances split per axis, O(n log n) instead of going
+    // through every pair
+    fn process_sum_of_distances(&self, expansion: &Expansion) -> u128 {
+        let positions = self.inflate(expansion);
+        sum_of_distances_along(positions.iter().map(|p| p.x).collect())
+            + sum_of_distances_along(positions.iter().map(|p| p.y).collect())
+    }
+
+    // Rotating by 45 degrees with u = x + y and v = x - y turns Chebyshev
+    // distances into half the Manhattan ones, max(|dx|, |dy|) being
+    // (|du| + |dv|) / 2. v is shifted by the highest y to stay positive.
+    fn process_sum_of_chebyshev(&self, expansion: &Expansion) -> u128 {
+        let positions = self.inflate(expansion);
+        let max_y = positions.iter().map(|p| p.y).max().unwrap_or(0);
+        (sum_of_distances_along(positions.iter().map(|p| p.x + p.y).collect())
+            + sum_of_distances_along(positions.iter().map(|p| p.x + max_y - p.y).collect()))
+            / 2
+    }
+
+    // No per-axis split here, every pair is visited
+    fn process_sum_of_euclidean(&self, expansion: &Expansion) -> f64 {
+        let positions = self.inflate(expansion);
+        let mut total = 0.0;
+        for (i, a) in positions.iter().enumerate() {
+            for b in &positions[i + 1..] {
+                total += Metric::Euclidean.distance(a, b);
+            }
         }
         total
     }
 
-    fn deinflate(&mut self) {
-        self.galaxies_inflated.clear();
-        self.galaxies_inflated.extend(&self.galaxies);
+    fn position_of(positions: &[Point], id: usize) -> Result<&Point, String> {
+        id.checked_sub(1)
+            .and_then(|i| positions.get(i))
+            .ok_or(format!(
+                "there is no galaxy {}, they go from 1 to {}",
+                id,
+                positions.len()
+            ))
+    }
+
+    fn distance(
+        &self,
+        a: usize,
+        b: usize,
+        expansion: &Expansion,
+        metric: Metric,
+    ) -> Result<f64, String> {
+        let positions = self.inflate(expansion);
+        Ok(metric.distance(
+            Universe::position_of(&positions, a)?,
+            Universe::position_of(&positions, b)?,
+        ))
+    }
+
+    // The k galaxies closest to galaxy n with their distances, closest
+    // first and by id on ties
+    fn nearest(
+        &self,
+        n: usize,
+        k: usize,
+        expansion: &Expansion,
+        metric: Metric,
+    ) -> Result<Vec<(usize, f64)>, String> {
+        let positions = self.inflate(expansion);
+        let from = Universe::position_of(&positions, n)?;
+        let mut distances: Vec<(usize, f64)> = positions
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i + 1 != n)
+            .map(|(i, position)| (i + 1, metric.distance(from, position)))
+            .collect();
+        distances.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        distances.truncate(k);
+        Ok(distances)
     }
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day11 <filename>");
+        println!("Usage: ./day11 <filename> [--factor <n>] [--row-factor <n>] [--col-factor <n>] [--spec <file>] [--metric <manhattan | euclidean | chebyshev>] [--distance <a> <b>] [--nearest <n> <k>]");
         std::process::exit(1);
     }
     let f = File::open(&args[1])?;
@@ -135,24 +292,100 @@ fn main() -> std::io::Result<()> {
 
     let mut universe = Universe::new();
 
-    let mut galaxy_count = 1;
     for (i, line) in reader.lines().enumerate() {
-        let line = line.unwrap();
+        let line = line?;
         for (j, c) in line.chars().enumerate() {
             if c == '#' {
-                universe.push_galaxy(Galaxy::from(galaxy_count, Point::from(j as u64, i as u64)));
-                galaxy_count += 1;
+                universe.push_galaxy(Point::from(j as u128, i as u128));
+            }
+        }
+    }
+
+    let fail = |message: String| -> ! {
+        eprintln!("Error: {}", message);
+        std::process::exit(1);
+    };
+    // Values following a flag, None if the flag is not given
+    let values_of = |flag: &str, count: usize| -> Option<Vec<u64>> {
+        let i = args.iter().position(|arg| arg == flag)?;
+        let values: Option<Vec<u64>> = args
+            .iter()
+            .skip(i + 1)
+            .take(count)
+            .map(|value| value.parse().ok())
+            .collect();
+        match values {
+            Some(values) if values.len() == count => Some(values),
+            _ => fail(format!("{} expects {} number(s)", flag, count)),
+        }
+    };
+
+    let mut expansion = Expansion::uniform(values_of("--factor", 1).map_or(2, |v| v[0]));
+    if let Some(v) = values_of("--row-factor", 1) {
+        expansion.rows.factor = v[0];
+    }
+    if let Some(v) = values_of("--col-factor", 1) {
+        expansion.cols.factor = v[0];
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--spec") {
+        let Some(spec) = args.get(i + 1) else {
+            fail("--spec expects a file".to_string())
+        };
+        if let Err(err) = expansion.apply_spec(BufReader::new(File::open(spec)?)) {
+            fail(err);
+        }
+    }
+
+    let metric = match args.iter().position(|arg| arg == "--metric") {
+        Some(i) => match args.get(i + 1).and_then(|arg| Metric::from_arg(arg)) {
+            Some(metric) => metric,
+            None => fail("--metric expects manhattan, euclidean or chebyshev".to_string()),
+        },
+        None => Metric::Manhattan,
+    };
+
+    let customised = [
+        "--factor",
+        "--row-factor",
+        "--col-factor",
+        "--spec",
+        "--metric",
+    ]
+    .iter()
+    .any(|flag| args.iter().any(|arg| arg == flag));
+    if customised {
+        let total = match metric {
+            Metric::Manhattan => universe.process_sum_of_distances(&expansion).to_string(),
+            Metric::Chebyshev => universe.process_sum_of_chebyshev(&expansion).to_string(),
+            Metric::Euclidean => format!("{:.3}", universe.process_sum_of_euclidean(&expansion)),
+        };
+        println!("Sum of {} distances: {}", metric, total);
+    }
+
+    if let Some(v) = values_of("--distance", 2) {
+        let (a, b) = (v[0] as usize, v[1] as usize);
+        match universe.distance(a, b, &expansion, metric) {
+            Ok(distance) => println!("Distance between galaxy {} and {}: {}", a, b, distance),
+            Err(err) => fail(err),
+        }
+    }
+
+    if let Some(v) = values_of("--nearest", 2) {
+        let (n, k) = (v[0] as usize, v[1] as usize);
+        match universe.nearest(n, k, &expansion, metric) {
+            Ok(nearest) => {
+                for (id, distance) in nearest {
+                    println!("Galaxy {} is at {} from galaxy {}", id, distance, n);
+                }
             }
+            Err(err) => fail(err),
         }
     }
 
-    universe.inflate(2);
-    let result1 = universe.process_sum_of_distances();
+    let result1 = universe.process_sum_of_distances(&Expansion::uniform(2));
     println!("Result for part 1: {}", result1);
 
-    universe.deinflate();
-    universe.inflate(1000000);
-    let result2 = universe.process_sum_of_distances();
+    let result2 = universe.process_sum_of_distances(&Expansion::uniform(1000000));
     println!("Result for part 2: {}", result2);
 
     Ok(())
@@ -1,135 +1,712 @@
-use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-fn calc_arragements(
-    spring_row: &str,
-    blocks: &Vec<u64>,
-    spring_idx: usize,
-    blocks_idx: usize,
-    current_block_pos: usize,
-    hash_table: &mut HashMap<(String, Vec<u64>, usize, usize, usize), u64>,
-) -> u64 {
-    let table_entry = (
-        spring_row.to_string(),
-        blocks.clone(),
-        spring_idx,
-        blocks_idx,
-        current_block_pos,
-    );
-
-    if let Some(cached) = hash_table.get(&table_entry) {
-        return *cached;
-    }
-
-    if spring_idx == spring_row.len() {
-        if blocks_idx == blocks.len() && current_block_pos == 0 {
-            return 1;
-        } else if blocks_idx == blocks.len() - 1
-            && blocks[blocks_idx] == current_block_pos.try_into().unwrap()
+// Unsigned integer of any size, unfolding rows many times gives counts well
+// past u128. Limbs are little endian without trailing zeros.
+#[derive(Clone, PartialEq, Eq)]
+struct Count {
+    limbs: Vec<u64>,
+}
+
+impl Count {
+    fn zero() -> Self {
+        Count { limbs: Vec::new() }
+    }
+
+    fn one() -> Self {
+        Count { limbs: vec![1] }
+    }
+
+    fn add(&self, other: &Count) -> Count {
+        let mut limbs: Vec<u64> = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = false;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let a = self.limbs.get(i).copied().unwrap_or(0);
+            let b = other.limbs.get(i).copied().unwrap_or(0);
+            let (sum, overflow1) = a.overflowing_add(b);
+            let (sum, overflow2) = sum.overflowing_add(carry as u64);
+            limbs.push(sum);
+            carry = overflow1 || overflow2;
+        }
+        if carry {
+            limbs.push(1);
+        }
+        Count { limbs }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    // Uniform in [0, self), drawing as many bits as self has and retrying
+    // when going past it, which happens less than half of the time
+    fn random_below(&self, rng: &mut Rng) -> Count {
+        let top = match self.limbs.last() {
+            Some(&top) => top,
+            None => return Count::zero(),
+        };
+        let mask = u64::MAX >> top.leading_zeros();
+        loop {
+            let mut limbs: Vec<u64> = self.limbs.iter().map(|_| rng.next_u64()).collect();
+            *limbs.last_mut().unwrap() &= mask;
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+            let candidate = Count { limbs };
+            if candidate < *self {
+                return candidate;
+            }
+        }
+    }
+}
+
+impl Ord for Count {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for Count {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// SplitMix64, enough for sampling and reproducible from a seed
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Peel off 19 decimal digits at a time, the most a u64 can hold
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+        let mut limbs = self.limbs.clone();
+        let mut chunks: Vec<u64> = Vec::new();
+        while !limbs.is_empty() {
+            let mut remainder: u128 = 0;
+            for limb in limbs.iter_mut().rev() {
+                let current = (remainder << 64) | *limb as u128;
+                *limb = (current / CHUNK) as u64;
+                remainder = current % CHUNK;
+            }
+            chunks.push(remainder as u64);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{:019}", chunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Number of ways to place the blocks in a spring row, for every suffix.
+// ways[i][b] counts the arrangements of blocks[b..] in springs[i..] when
+// position i may start a block, i.e. the spring before it is not damaged.
+struct Arrangements {
+    springs: Vec<u8>,
+    blocks: Vec<usize>,
+    // Number of operational springs '.' before every position
+    operational_before: Vec<usize>,
+    ways: Vec<Count>,
+}
+
+impl Arrangements {
+    fn new(spring_row: &str, blocks: &[u64]) -> Self {
+        let springs = spring_row.as_bytes().to_vec();
+        let mut operational_before = vec![0; springs.len() + 1];
+        for (i, &spring) in springs.iter().enumerate() {
+            operational_before[i + 1] = operational_before[i] + (spring == b'.') as usize;
+        }
+
+        let mut arrangements = Arrangements {
+            ways: vec![Count::zero(); (springs.len() + 1) * (blocks.len() + 1)],
+            springs,
+            blocks: blocks.iter().map(|&block| block as usize).collect(),
+            operational_before,
+        };
+        arrangements.fill();
+        arrangements
+    }
+
+    fn index(&self, spring_idx: usize, blocks_idx: usize) -> usize {
+        spring_idx * (self.blocks.len() + 1) + blocks_idx
+    }
+
+    fn ways_from(&self, spring_idx: usize, blocks_idx: usize) -> &Count {
+        &self.ways[self.index(spring_idx, blocks_idx)]
+    }
+
+    // Where the row continues after placing blocks[blocks_idx] at
+    // spring_idx, skipping the operational spring that has to follow it.
+    // None if the block does not fit there.
+    fn after_block(&self, spring_idx: usize, blocks_idx: usize) -> Option<usize> {
+        let end = spring_idx + *self.blocks.get(blocks_idx)?;
+        if end > self.springs.len()
+            || self.operational_before[end] != self.operational_before[spring_idx]
         {
-            // There is a block at last
-            return 1;
-        } else {
-            return 0;
-        }
-    }
-
-    let mut arrangements = 0;
-    let current_char = spring_row.chars().nth(spring_idx).unwrap();
-
-    for c in ['.', '#'] {
-        if current_char == c || current_char == '?' {
-            // Send .
-            if c == '.' && current_block_pos == 0 {
-                arrangements += calc_arragements(
-                    spring_row,
-                    blocks,
-                    spring_idx + 1,
-                    blocks_idx,
-                    0,
-                    hash_table,
-                );
-            } else if c == '.'
-                && current_block_pos > 0
-                && blocks_idx < blocks.len()
-                && blocks[blocks_idx] == current_block_pos as u64
-            {
-                arrangements += calc_arragements(
-                    spring_row,
-                    blocks,
-                    spring_idx + 1,
-                    blocks_idx + 1,
-                    0,
-                    hash_table,
-                );
-            // Send #
-            } else if c == '#' {
-                arrangements += calc_arragements(
-                    spring_row,
-                    blocks,
-                    spring_idx + 1,
-                    blocks_idx,
-                    current_block_pos + 1,
-                    hash_table,
-                );
-            }
-        }
-    }
-
-    hash_table.insert(table_entry, arrangements);
-    arrangements
-}
-
-fn parse_line(line: &str) -> (String, Vec<u64>) {
+            return None;
+        }
+        match self.springs.get(end) {
+            None => Some(end),
+            Some(b'#') => None,
+            Some(_) => Some(end + 1),
+        }
+    }
+
+    fn fill(&mut self) {
+        let springs = self.springs.len();
+        let blocks = self.blocks.len();
+        let last = self.index(springs, blocks);
+        self.ways[last] = Count::one();
+
+        for spring_idx in (0..springs).rev() {
+            for blocks_idx in 0..=blocks {
+                let mut arrangements = Count::zero();
+                // Leave the spring operational
+                if self.springs[spring_idx] != b'#' {
+                    arrangements = self.ways_from(spring_idx + 1, blocks_idx).clone();
+                }
+                // Start the next block here
+                if let Some(next) = self.after_block(spring_idx, blocks_idx) {
+                    arrangements = arrangements.add(self.ways_from(next, blocks_idx + 1));
+                }
+                let index = self.index(spring_idx, blocks_idx);
+                self.ways[index] = arrangements;
+            }
+        }
+    }
+
+    fn count(&self) -> &Count {
+        self.ways_from(0, 0)
+    }
+
+    // Next state when leaving the spring operational, if that still leads
+    // to at least one arrangement
+    fn operational_step(&self, spring_idx: usize, blocks_idx: usize) -> Option<(usize, usize)> {
+        if self.springs[spring_idx] == b'#' || self.ways_from(spring_idx + 1, blocks_idx).is_zero()
+        {
+            return None;
+        }
+        Some((spring_idx + 1, blocks_idx))
+    }
+
+    // Next state when starting the next block at the spring, same condition
+    fn block_step(&self, spring_idx: usize, blocks_idx: usize) -> Option<(usize, usize)> {
+        let next = self.after_block(spring_idx, blocks_idx)?;
+        if self.ways_from(next, blocks_idx + 1).is_zero() {
+            return None;
+        }
+        Some((next, blocks_idx + 1))
+    }
+
+    // Writes the springs covered by a step, a block and the operational
+    // spring following it unless it ends the row
+    fn write_step(&self, row: &mut Vec<u8>, spring_idx: usize, step: Step, next: usize) {
+        match step {
+            Step::Operational => row.push(b'.'),
+            Step::Block(blocks_idx) => {
+                let end = spring_idx + self.blocks[blocks_idx];
+                row.resize(end, b'#');
+                row.resize(next, b'.');
+            }
+        }
+    }
+
+    fn iter(&self) -> ArrangementIter<'_> {
+        let mut iter = ArrangementIter {
+            arrangements: self,
+            row: Vec::with_capacity(self.springs.len()),
+            stack: Vec::new(),
+            empty_row: self.springs.is_empty() && !self.count().is_zero(),
+        };
+        if !self.springs.is_empty() {
+            iter.push_steps(0, 0);
+        }
+        iter
+    }
+
+    // Walks down the table picking each step with probability proportional
+    // to the arrangements it leads to, so every arrangement is equally likely
+    fn sample(&self, rng: &mut Rng) -> Option<String> {
+        if self.count().is_zero() {
+            return None;
+        }
+        let mut row: Vec<u8> = Vec::with_capacity(self.springs.len());
+        let (mut spring_idx, mut blocks_idx) = (0, 0);
+        while spring_idx < self.springs.len() {
+            let pick = self.ways_from(spring_idx, blocks_idx).random_below(rng);
+            let (step, next) = match self.operational_step(spring_idx, blocks_idx) {
+                Some(next) if pick < *self.ways_from(next.0, next.1) => (Step::Operational, next),
+                _ => (
+                    Step::Block(blocks_idx),
+                    self.block_step(spring_idx, blocks_idx).unwrap(),
+                ),
+            };
+            self.write_step(&mut row, spring_idx, step, next.0);
+            (spring_idx, blocks_idx) = next;
+        }
+        Some(String::from_utf8(row).unwrap())
+    }
+
+    // The row with every spring that is the same in all the arrangements
+    // shown as such, '?' for the others. None without any arrangement.
+    fn forced(&self) -> Option<String> {
+        if self.count().is_zero() {
+            return None;
+        }
+
+        // States reachable from the start that still lead to an arrangement
+        let springs = self.springs.len();
+        let mut reachable = vec![false; self.ways.len()];
+        reachable[self.index(0, 0)] = true;
+        let mut can_be_operational = vec![false; springs];
+        // Difference array of the springs covered by a damaged block
+        let mut damaged_cover = vec![0i64; springs + 1];
+
+        for spring_idx in 0..springs {
+            for blocks_idx in 0..=self.blocks.len() {
+                if !reachable[self.index(spring_idx, blocks_idx)] {
+                    continue;
+                }
+                if let Some((next, next_blocks)) = self.operational_step(spring_idx, blocks_idx) {
+                    can_be_operational[spring_idx] = true;
+                    let index = self.index(next, next_blocks);
+                    reachable[index] = true;
+                }
+                if let Some((next, next_blocks)) = self.block_step(spring_idx, blocks_idx) {
+                    let end = spring_idx + self.blocks[blocks_idx];
+                    damaged_cover[spring_idx] += 1;
+                    damaged_cover[end] -= 1;
+                    if end < springs {
+                        can_be_operational[end] = true;
+                    }
+                    let index = self.index(next, next_blocks);
+                    reachable[index] = true;
+                }
+            }
+        }
+
+        let mut covered = 0;
+        let row: String = (0..springs)
+            .map(|i| {
+                covered += damaged_cover[i];
+                match (covered > 0, can_be_operational[i]) {
+                    (true, false) => '#',
+                    (false, true) => '.',
+                    _ => '?',
+                }
+            })
+            .collect();
+        Some(row)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Operational,
+    // Placing the block with that index
+    Block(usize),
+}
+
+// Depth first walk of the table, only following steps that lead to an
+// arrangement so every branch yields one. `row` holds the springs decided
+// so far, a step taken at spring i starts by cutting it back to i springs.
+struct ArrangementIter<'a> {
+    arrangements: &'a Arrangements,
+    row: Vec<u8>,
+    stack: Vec<(usize, Step, (usize, usize))>,
+    empty_row: bool,
+}
+
+impl ArrangementIter<'_> {
+    fn push_steps(&mut self, spring_idx: usize, blocks_idx: usize) {
+        // Pushed in reverse so operational springs come first
+        if let Some(next) = self.arrangements.block_step(spring_idx, blocks_idx) {
+            self.stack.push((spring_idx, Step::Block(blocks_idx), next));
+        }
+        if let Some(next) = self.arrangements.operational_step(spring_idx, blocks_idx) {
+            self.stack.push((spring_idx, Step::Operational, next));
+        }
+    }
+}
+
+impl Iterator for ArrangementIter<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.empty_row {
+            self.empty_row = false;
+            return Some(String::new());
+        }
+        while let Some((spring_idx, step, next)) = self.stack.pop() {
+            self.row.truncate(spring_idx);
+            self.arrangements
+                .write_step(&mut self.row, spring_idx, step, next.0);
+            if next.0 == self.arrangements.springs.len() {
+                return Some(String::from_utf8(self.row.clone()).unwrap());
+            }
+            self.push_steps(next.0, next.1);
+        }
+        None
+    }
+}
+
+fn calc_arragements(spring_row: &str, blocks: &[u64]) -> Count {
+    Arrangements::new(spring_row, blocks).count().clone()
+}
+
+fn parse_line(line: &str) -> Result<(String, Vec<u64>), String> {
     let mut splited = line.split_whitespace();
-    let spring_row = splited.next().unwrap().to_string();
-    let blocks = splited
-        .next()
-        .unwrap()
+    let (Some(spring_row), Some(blocks), None) = (splited.next(), splited.next(), splited.next())
+    else {
+        return Err("expected <springs> <blocks>".to_string());
+    };
+    if let Some(spring) = spring_row.chars().find(|c| !"#.?".contains(*c)) {
+        return Err(format!(
+            "unknown spring '{}', expected '#', '.' or '?'",
+            spring
+        ));
+    }
+    let blocks = blocks
         .split(",")
-        .map(|c| c.parse::<u64>().unwrap())
-        .collect();
+        .map(|c| {
+            c.parse::<u64>()
+                .map_err(|_| format!("'{}' is not a block length", c))
+        })
+        .collect::<Result<Vec<u64>, String>>()?;
 
-    (spring_row, blocks)
+    Ok((spring_row.to_string(), blocks))
 }
 
-fn spring_extend(spring_row: &str, blocks: &Vec<u64>) -> (String, Vec<u64>) {
-    let mut extended_spring = spring_row.to_string();
-    let mut extended_blocks = blocks.clone();
+// A picross puzzle, every row and column being a spring row of its own with
+// '#' for filled cells. The puzzle file lists the rows then, after a blank
+// line, the columns, each one as `<cells> <blocks>` like the spring rows,
+// with '?' for cells not known in advance and 0 for a line without blocks.
+#[derive(Clone)]
+struct Nonogram {
+    width: usize,
+    height: usize,
+    row_clues: Vec<Vec<u64>>,
+    col_clues: Vec<Vec<u64>>,
+    cells: Vec<u8>,
+}
+
+impl Nonogram {
+    fn parse<R: BufRead>(reader: R) -> Result<Self, String> {
+        let mut sections: Vec<Vec<(String, Vec<u64>)>> = vec![Vec::new()];
+        for (line_num, line) in reader.lines().enumerate() {
+            let line = line.map_err(|err| err.to_string())?;
+            if line.trim().is_empty() {
+                if !sections.last().unwrap().is_empty() {
+                    sections.push(Vec::new());
+                }
+                continue;
+            }
+            let (cells, mut blocks) =
+                parse_line(&line).map_err(|err| format!("line {}: {}", line_num + 1, err))?;
+            blocks.retain(|&block| block > 0);
+            sections.last_mut().unwrap().push((cells, blocks));
+        }
+        sections.retain(|section| !section.is_empty());
+        let [rows, cols] = sections.as_slice() else {
+            return Err("expected the rows and the columns, separated by a blank line".to_string());
+        };
+
+        let (height, width) = (rows.len(), cols.len());
+        let mut cells = vec![b'?'; width * height];
+        for (i, (row, _)) in rows.iter().enumerate() {
+            if row.len() != width {
+                return Err(format!(
+                    "row {} has {} cells, expected {}",
+                    i + 1,
+                    row.len(),
+                    width
+                ));
+            }
+            cells[i * width..(i + 1) * width].copy_from_slice(row.as_bytes());
+        }
+        for (j, (col, _)) in cols.iter().enumerate() {
+            if col.len() != height {
+                return Err(format!(
+                    "column {} has {} cells, expected {}",
+                    j + 1,
+                    col.len(),
+                    height
+                ));
+            }
+            for (i, cell) in col.bytes().enumerate() {
+                let known = &mut cells[i * width + j];
+                match (*known, cell) {
+                    (_, b'?') => {}
+                    (b'?', _) => *known = cell,
+                    (a, b) if a != b => {
+                        return Err(format!(
+                            "row {} and column {} disagree on their cell",
+                            i + 1,
+                            j + 1
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+        }
 
-    for _i in 0..4 {
-        extended_spring.push('?');
-        extended_spring.push_str(spring_row);
-        extended_blocks.extend(blocks);
+        Ok(Nonogram {
+            width,
+            height,
+            row_clues: rows.iter().map(|(_, blocks)| blocks.clone()).collect(),
+            col_clues: cols.iter().map(|(_, blocks)| blocks.clone()).collect(),
+            cells,
+        })
     }
 
+    // Lines 0..height are the rows, the columns follow
+    fn line_cells(&self, line: usize) -> Vec<usize> {
+        if line < self.height {
+            (0..self.width).map(|j| line * self.width + j).collect()
+        } else {
+            let j = line - self.height;
+            (0..self.height).map(|i| i * self.width + j).collect()
+        }
+    }
+
+    fn clues(&self, line: usize) -> &[u64] {
+        if line < self.height {
+            &self.row_clues[line]
+        } else {
+            &self.col_clues[line - self.height]
+        }
+    }
+
+    // Solves lines one at a time with the cells forced in all their
+    // arrangements, going back to the crossing lines of every cell found.
+    // false if some line has no arrangement left.
+    fn propagate(&mut self) -> bool {
+        let lines = self.height + self.width;
+        let mut queued = vec![true; lines];
+        let mut queue: VecDeque<usize> = (0..lines).collect();
+
+        while let Some(line) = queue.pop_front() {
+            queued[line] = false;
+            let cells = self.line_cells(line);
+            let current: String = cells.iter().map(|&c| self.cells[c] as char).collect();
+            let Some(forced) = Arrangements::new(&current, self.clues(line)).forced() else {
+                return false;
+            };
+
+            for (k, (&cell, forced)) in cells.iter().zip(forced.bytes()).enumerate() {
+                if self.cells[cell] != b'?' || forced == b'?' {
+                    continue;
+                }
+                self.cells[cell] = forced;
+                let crossing = if line < self.height {
+                    self.height + k
+                } else {
+                    k
+                };
+                if !queued[crossing] {
+                    queued[crossing] = true;
+                    queue.push_back(crossing);
+                }
+            }
+        }
+        true
+    }
+
+    // Propagates, then guesses the first unknown cell when stuck, up to
+    // `limit` solutions
+    fn solve(&self, limit: usize, solutions: &mut Vec<Nonogram>) {
+        let mut grid = self.clone();
+        if !grid.propagate() {
+            return;
+        }
+        let Some(unknown) = grid.cells.iter().position(|&cell| cell == b'?') else {
+            solutions.push(grid);
+            return;
+        };
+        for guess in [b'#', b'.'] {
+            if solutions.len() >= limit {
+                return;
+            }
+            let mut guessed = grid.clone();
+            guessed.cells[unknown] = guess;
+            guessed.solve(limit, solutions);
+        }
+    }
+
+    // The grid with the row clues on the right and the column clues below
+    fn render(&self) -> String {
+        let mut picture = String::new();
+        for (row, clues) in self.cells.chunks(self.width).zip(&self.row_clues) {
+            picture.extend(row.iter().map(|&cell| cell as char));
+            let clues: Vec<String> = clues.iter().map(|block| block.to_string()).collect();
+            picture.push_str(&format!("  {}\n", clues.join(" ")));
+        }
+
+        let depth = self
+            .col_clues
+            .iter()
+            .map(|clues| clues.len())
+            .max()
+            .unwrap_or(0);
+        for k in 0..depth {
+            picture.push('\n');
+            for clues in &self.col_clues {
+                // Clues are bottom aligned, only single digits fit a column
+                let offset = depth - clues.len();
+                match k.checked_sub(offset).map(|k| clues[k]) {
+                    Some(block) if block < 10 => picture.push_str(&block.to_string()),
+                    Some(_) => picture.push('+'),
+                    None => picture.push(' '),
+                }
+            }
+        }
+        picture.push('\n');
+        picture
+    }
+}
+
+// The row repeated `factor` times, joined by unknown springs
+fn spring_extend(spring_row: &str, blocks: &[u64], factor: usize) -> (String, Vec<u64>) {
+    let extended_spring = vec![spring_row; factor].join("?");
+    let extended_blocks = blocks.repeat(factor);
+
     (extended_spring, extended_blocks)
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day12 <filename>");
+        println!(
+            "Usage: ./day12 <filename> [--unfold <factor>] [--list <limit>] [--sample <seed>] [--forced] | --nonogram"
+        );
         std::process::exit(1);
     }
 
+    let unfold_factor = match args.iter().position(|arg| arg == "--unfold") {
+        Some(i) => match args
+            .get(i + 1)
+            .and_then(|factor| factor.parse::<usize>().ok())
+        {
+            Some(factor) if factor > 0 => factor,
+            _ => {
+                eprintln!("Error: --unfold expects a positive factor");
+                std::process::exit(1);
+            }
+        },
+        None => 5,
+    };
+
+    let number_after = |flag: &str| -> Option<u64> {
+        let i = args.iter().position(|arg| arg == flag)?;
+        match args.get(i + 1).and_then(|n| n.parse().ok()) {
+            Some(n) => Some(n),
+            None => {
+                eprintln!("Error: {} expects a number", flag);
+                std::process::exit(1);
+            }
+        }
+    };
+    let list_limit = number_after("--list");
+    let mut rng = number_after("--sample").map(Rng::new);
+    let show_forced = args.iter().any(|arg| arg == "--forced");
+    let details = list_limit.is_some() || rng.is_some() || show_forced;
+
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
-    let mut sum_of_arrangements1 = 0;
-    let mut sum_of_arrangements2 = 0;
-    let mut hash_table: HashMap<(String, Vec<u64>, usize, usize, usize), u64> = HashMap::new();
-    for line in reader.lines() {
-        let (spring_row, blocks) = parse_line(&line?);
-        let arragements1 = calc_arragements(&spring_row, &blocks, 0, 0, 0, &mut hash_table);
-        sum_of_arrangements1 += arragements1;
-
-        hash_table.clear();
-        let (extended_spring, extended_block) = spring_extend(&spring_row, &blocks);
-        let arragements2 = calc_arragements(&extended_spring, &extended_block, 0, 0, 0, &mut hash_table);
-        sum_of_arrangements2 += arragements2;
+    if args.iter().any(|arg| arg == "--nonogram") {
+        let nonogram = match Nonogram::parse(reader) {
+            Ok(nonogram) => nonogram,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        };
+        let mut solutions: Vec<Nonogram> = Vec::new();
+        nonogram.solve(2, &mut solutions);
+        match solutions.first() {
+            None => println!("No solution"),
+            Some(solution) => {
+                if solutions.len() > 1 {
+                    eprintln!("warning: the puzzle has several solutions, showing the first one");
+                }
+                print!("{}", solution.render());
+            }
+        }
+        return Ok(());
+    }
+
+    let mut sum_of_arrangements1 = Count::zero();
+    let mut sum_of_arrangements2 = Count::zero();
+    for (i, line) in reader.lines().enumerate() {
+        let (spring_row, blocks) = match parse_line(&line?) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("Error: line {}: {}", i + 1, err);
+                std::process::exit(1);
+            }
+        };
+        let arrangements = Arrangements::new(&spring_row, &blocks);
+        sum_of_arrangements1 = sum_of_arrangements1.add(arrangements.count());
+
+        if details {
+            println!(
+                "Row {}: {} - {} arrangements",
+                i + 1,
+                spring_row,
+                arrangements.count()
+            );
+            if let Some(limit) = list_limit {
+                for arrangement in arrangements.iter().take(limit as usize) {
+                    println!("  {}", arrangement);
+                }
+            }
+            if let Some(rng) = rng.as_mut() {
+                if let Some(sample) = arrangements.sample(rng) {
+                    println!("  sample: {}", sample);
+                }
+            }
+            if show_forced {
+                if let Some(forced) = arrangements.forced() {
+                    println!("  forced: {}", forced);
+                }
+            }
+        }
+
+        let (extended_spring, extended_block) = spring_extend(&spring_row, &blocks, unfold_factor);
+        let arragements2 = calc_arragements(&extended_spring, &extended_block);
+        sum_of_arrangements2 = sum_of_arrangements2.add(&arragements2);
     }
 
     let result1 = sum_of_arrangements1;
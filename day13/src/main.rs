@@ -2,152 +2,231 @@ use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
+// A line of cells packed 64 per word, bit i standing for cell i and set
+// for '#'
+type Bits = Vec<u64>;
+
+fn pack(cells: impl Iterator<Item = bool>, len: usize) -> Bits {
+    let mut bits: Bits = vec![0; len.div_ceil(64)];
+    for (i, cell) in cells.enumerate() {
+        if cell {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+fn get_bit(bits: &Bits, i: usize) -> bool {
+    bits[i / 64] >> (i % 64) & 1 == 1
+}
+
+// The first `len` cells in reverse order
+fn reversed(bits: &Bits, len: usize) -> Bits {
+    pack((0..len).rev().map(|i| get_bit(bits, i)), len)
+}
+
 struct Pattern {
-    rows: Vec<String>,
-    cols: Vec<String>,
-    width: i32,
-    height: i32,
+    rows: Vec<Bits>,
+    cols: Vec<Bits>,
+    width: usize,
+    height: usize,
 }
 
 impl Pattern {
-    fn new() -> Self {
+    fn from(pattern_str: &str) -> Self {
+        let lines: Vec<&str> = pattern_str
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let height = lines.len();
+        // Cells missing from short lines count as ash
+        let cell = |i: usize, j: usize| lines[i].as_bytes().get(j) == Some(&b'#');
+
         Pattern {
-            rows: Vec::new(),
-            cols: Vec::new(),
-            width: 0,
-            height: 0,
+            rows: (0..height)
+                .map(|i| pack((0..width).map(|j| cell(i, j)), width))
+                .collect(),
+            cols: (0..width)
+                .map(|j| pack((0..height).map(|i| cell(i, j)), height))
+                .collect(),
+            width,
+            height,
         }
     }
 
-    fn from(pattern_str: &str, width: i32) -> Self {
-        let mut p = Pattern::new();
-        let mut first_iteration = true;
-        p.width = width;
-        for i in 0..width {
-            let i = i as usize;
-            let mut vert_str = String::new();
-            for line in pattern_str.lines() {
-                if first_iteration {
-                    if !line.trim().is_empty() {
-                        p.rows.push(line.to_string());
-                        p.height += 1;
-                    }
-                }
-                if let Some(c) = line.chars().nth(i) {
-                    vert_str.push(c);
+    fn get(&self, i: usize, j: usize) -> bool {
+        get_bit(&self.rows[i], j)
+    }
+
+    // Cells on one side of the main diagonal that differ from their
+    // reflection across it, row i being the same as column i when there is
+    // none. None if the pattern is not square.
+    fn transpose_flips(&self) -> Option<Vec<(usize, usize)>> {
+        if self.width != self.height {
+            return None;
+        }
+        let mut flips: Vec<(usize, usize)> = Vec::new();
+        for (i, (row, col)) in self.rows.iter().zip(&self.cols).enumerate() {
+            // Every mismatch shows in both row i and column i, keep the one
+            // above the diagonal
+            flips.extend(
+                diff(row, col)
+                    .into_iter()
+                    .filter(|&j| j > i)
+                    .map(|j| (i, j)),
+            );
+        }
+        Some(flips)
+    }
+
+    // Cells that differ from the one they land on after a half turn, row i
+    // having to match row height - 1 - i read backwards. Only the first cell
+    // of each pair is kept.
+    fn rotation_flips(&self) -> Vec<(usize, usize)> {
+        let mut flips: Vec<(usize, usize)> = Vec::new();
+        for i in 0..self.height.div_ceil(2) {
+            let opposite = reversed(&self.rows[self.height - 1 - i], self.width);
+            for j in diff(&self.rows[i], &opposite) {
+                if (i, j) < (self.height - 1 - i, self.width - 1 - j) {
+                    flips.push((i, j));
                 }
             }
-            p.cols.push(vert_str);
-            first_iteration = false;
         }
-        p
+        flips
     }
 }
 
-#[derive(Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
 enum SymmetryType {
     VerticalSymmetry,
     HorizontalSymmetry,
 }
 
-fn process_symmetry_value(val: (i32, SymmetryType)) -> i32 {
-    if val.1 == SymmetryType::HorizontalSymmetry {
-        val.0 * 100
-    } else {
-        val.0
+impl std::fmt::Display for SymmetryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SymmetryType::VerticalSymmetry => write!(f, "vertical"),
+            SymmetryType::HorizontalSymmetry => write!(f, "horizontal"),
+        }
     }
 }
 
-fn diff(str1: &str, str2: &str) -> i32 {
-    str1.chars()
-        .zip(str2.chars())
-        .filter(|(c1, c2)| c1 != c2)
-        .count()
-        .try_into()
-        .unwrap()
-}
-
-fn check_mirrors(symmetry_axis: &Vec<String>, symmetry_center: usize, smudge: bool) -> i32 {
-    let mut back: i32 = symmetry_center as i32;
-    let mut front: i32 = symmetry_center as i32 + 1;
-    let axis_len = symmetry_axis.len() as i32;
-    let mut smudges = 0;
-    let mut count = 0;
-    while back >= 0 && front < axis_len {
-        let front_str = &symmetry_axis[front as usize];
-        let back_str = &symmetry_axis[back as usize];
-
-        if front_str == back_str {
-            count += 1
-        } else if smudge && smudges < 1 {
-            let diff_count = diff(&back_str, &front_str);
-            if diff_count == 1 {
-                smudges += 1;
-                count += 1;
-            } else {
-                return 0;
-            }
-        } else {
-            return 0;
-        }
-        back -= 1;
-        front += 1;
+struct MirrorAxis {
+    kind: SymmetryType,
+    // Rows above a horizontal axis, or columns left of a vertical one
+    position: usize,
+    // Pairs of lines reflected onto each other before reaching an edge
+    extent: usize,
+    // Cells (row, column) to flip for a perfect reflection, always the one
+    // above or left of the axis
+    flips: Vec<(usize, usize)>,
+}
+
+fn process_symmetry_value(axis: &MirrorAxis) -> usize {
+    if axis.kind == SymmetryType::HorizontalSymmetry {
+        axis.position * 100
+    } else {
+        axis.position
     }
-    if smudge {
-        if smudges == 1 {
-            return count;
-        } else {
-            return 0;
+}
+
+fn mismatches(bits1: &Bits, bits2: &Bits) -> usize {
+    bits1
+        .iter()
+        .zip(bits2)
+        .map(|(a, b)| (a ^ b).count_ones() as usize)
+        .sum()
+}
+
+// Positions where two lines differ
+fn diff(bits1: &Bits, bits2: &Bits) -> Vec<usize> {
+    let mut positions: Vec<usize> = Vec::new();
+    for (w, (a, b)) in bits1.iter().zip(bits2).enumerate() {
+        let mut word = a ^ b;
+        while word != 0 {
+            positions.push(w * 64 + word.trailing_zeros() as usize);
+            word &= word - 1;
         }
     }
-    count
-}
-
-fn process_perfect_symmetry(pattern: &Pattern, smudge: bool) -> (i32, SymmetryType) {
-    let mut last_row = String::default();
-    let mut mirrored_rows = 0i32;
-    let mut last_row_sum = 0;
-    for (i, row) in pattern.rows.iter().enumerate() {
-        let i = i as i32;
-        if last_row == *row || (smudge && diff(&last_row, &row) == 1) {
-            let current_sum = check_mirrors(&pattern.rows, (i - 1).try_into().unwrap(), smudge);
-            if current_sum > last_row_sum {
-                mirrored_rows = i - 1;
-                last_row_sum = current_sum;
+    positions
+}
+
+// Every axis between two lines where the reflection up to the nearest edge
+// is off by exactly `smudges` cells
+fn mirror_axes_along(lines: &[Bits], kind: SymmetryType, smudges: usize) -> Vec<MirrorAxis> {
+    let mut axes: Vec<MirrorAxis> = Vec::new();
+    for position in 1..lines.len() {
+        let extent = position.min(lines.len() - position);
+        let pairs = || (0..extent).map(|k| (position - 1 - k, position + k));
+
+        // Counting first, the cells are only listed for actual axes
+        let mut count = 0;
+        for (back, front) in pairs() {
+            count += mismatches(&lines[back], &lines[front]);
+            if count > smudges {
+                break;
             }
         }
-        last_row = row.to_string();
-    }
-
-    let mut last_col = String::default();
-    let mut mirrored_cols = 0i32;
-    let mut last_col_sum = 0;
-    for (i, col) in pattern.cols.iter().enumerate() {
-        let i = i as i32;
-        if last_col == *col || (smudge && diff(&last_col, &col) == 1) {
-            let current_sum = check_mirrors(&pattern.cols, (i - 1).try_into().unwrap(), smudge);
-            if current_sum > last_row_sum {
-                mirrored_cols = i - 1;
-                last_col_sum = current_sum;
+        if count == smudges {
+            let mut flips: Vec<(usize, usize)> = Vec::new();
+            for (back, front) in pairs() {
+                for i in diff(&lines[back], &lines[front]) {
+                    flips.push(match kind {
+                        SymmetryType::HorizontalSymmetry => (back, i),
+                        SymmetryType::VerticalSymmetry => (i, back),
+                    });
+                }
             }
+            axes.push(MirrorAxis {
+                kind,
+                position,
+                extent,
+                flips,
+            });
         }
-        last_col = col.to_string();
     }
+    axes
+}
 
-    mirrored_cols += 1;
-    mirrored_rows += 1;
+fn mirror_axes(pattern: &Pattern, smudges: usize) -> Vec<MirrorAxis> {
+    let mut axes = mirror_axes_along(&pattern.rows, SymmetryType::HorizontalSymmetry, smudges);
+    axes.extend(mirror_axes_along(
+        &pattern.cols,
+        SymmetryType::VerticalSymmetry,
+        smudges,
+    ));
+    axes
+}
 
-    if last_col_sum > last_row_sum {
-        (mirrored_cols, SymmetryType::VerticalSymmetry)
-    } else {
-        (mirrored_rows, SymmetryType::HorizontalSymmetry)
+// The axis reflecting the most lines, horizontal ones first on ties
+fn process_symmetry(pattern: &Pattern, smudges: usize) -> Option<MirrorAxis> {
+    mirror_axes(pattern, smudges)
+        .into_iter()
+        .max_by_key(|axis| (axis.extent, axis.kind))
+}
+
+// The rows of the pattern with the given cells flipped
+fn corrected_rows(pattern: &Pattern, flips: &[(usize, usize)]) -> Vec<String> {
+    let mut rows: Vec<Vec<char>> = (0..pattern.height)
+        .map(|i| {
+            (0..pattern.width)
+                .map(|j| if pattern.get(i, j) { '#' } else { '.' })
+                .collect()
+        })
+        .collect();
+    for &(i, j) in flips {
+        rows[i][j] = if rows[i][j] == '#' { '.' } else { '#' };
     }
+    rows.into_iter()
+        .map(|row| row.into_iter().collect())
+        .collect()
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day13 <filename>");
+        println!("Usage: ./day13 <filename> [--smudges <k> [--show]] [--symmetries]");
         std::process::exit(1);
     }
 
@@ -157,41 +236,82 @@ fn main() -> std::io::Result<()> {
     let mut current_pattern = String::new();
     let mut patterns: Vec<Pattern> = Vec::new();
 
-    let mut current_width = 0;
     for line in reader.lines() {
-        let line = line.unwrap();
-        current_width = if line.len() != 0 {
-            line.len() as i32
-        } else {
-            current_width
-        };
+        let line = line?;
         current_pattern.push_str(&line);
         current_pattern.push('\n');
         if line.trim().is_empty() {
-            patterns.push(Pattern::from(&current_pattern, current_width));
+            patterns.push(Pattern::from(&current_pattern));
             current_pattern.clear();
         }
     }
 
     if !current_pattern.trim().is_empty() {
-        patterns.push(Pattern::from(&current_pattern, current_width));
+        patterns.push(Pattern::from(&current_pattern));
         current_pattern.clear();
     }
 
-    let result1: i32 = patterns
+    if let Some(i) = args.iter().position(|arg| arg == "--smudges") {
+        let Some(smudges) = args.get(i + 1).and_then(|k| k.parse::<usize>().ok()) else {
+            eprintln!("Error: --smudges expects a number of cells");
+            std::process::exit(1);
+        };
+        let show = args.iter().any(|arg| arg == "--show");
+        for (n, pattern) in patterns.iter().enumerate() {
+            let axes = mirror_axes(pattern, smudges);
+            println!("Pattern {}: {} axes", n + 1, axes.len());
+            for axis in &axes {
+                println!(
+                    "  {} axis after {} {}, extent {}, flips {:?}",
+                    axis.kind,
+                    match axis.kind {
+                        SymmetryType::HorizontalSymmetry => "row",
+                        SymmetryType::VerticalSymmetry => "column",
+                    },
+                    axis.position,
+                    axis.extent,
+                    axis.flips
+                );
+                if show && !axis.flips.is_empty() {
+                    for row in corrected_rows(pattern, &axis.flips) {
+                        println!("    {}", row);
+                    }
+                }
+            }
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--symmetries") {
+        let describe = |flips: &[(usize, usize)]| {
+            if flips.is_empty() {
+                "symmetric".to_string()
+            } else {
+                format!("off by {} cells {:?}", flips.len(), flips)
+            }
+        };
+        for (n, pattern) in patterns.iter().enumerate() {
+            let diagonal = pattern
+                .transpose_flips()
+                .map_or("not square".to_string(), |flips| describe(&flips));
+            println!(
+                "Pattern {}: diagonal {}, rotational {}",
+                n + 1,
+                diagonal,
+                describe(&pattern.rotation_flips())
+            );
+        }
+    }
+
+    let result1: usize = patterns
         .iter()
-        .map(|p| {
-            let sym = process_perfect_symmetry(p, false);
-            process_symmetry_value(sym)
-        })
+        .filter_map(|p| process_symmetry(p, 0))
+        .map(|axis| process_symmetry_value(&axis))
         .sum();
 
-    let result2: i32 = patterns
+    let result2: usize = patterns
         .iter()
-        .map(|p| {
-            let sym = process_perfect_symmetry(p, true);
-            process_symmetry_value(sym)
-        })
+        .filter_map(|p| process_symmetry(p, 1))
+        .map(|axis| process_symmetry_value(&axis))
         .sum();
 
     println!("Result for part 1: {}", result1);
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -7,6 +8,7 @@ struct PossibleNum {
     start: i32,
     line: i32,
     num: i32,
+    valid: bool,
 }
 
 struct SymPosition {
@@ -14,109 +16,219 @@ struct SymPosition {
     line: i32,
 }
 
-impl PossibleNum {
-    fn is_adjacent_to_sym(&self, sym: &SymPosition) -> bool {
-        let mut is_adj = false;
-        if sym.line == self.line - 1 || sym.line == self.line + 1 {
-            is_adj = sym.idx >= self.start - 1 && sym.idx <= self.end + 1;
-        }
-
-        is_adj = if is_adj {
-            is_adj
-        } else {
-            sym.line == self.line && (sym.idx == self.start - 1 || sym.idx == self.end + 1)
-        };
+// One schematic line kept in the sliding window. `cells` maps every column to
+// the id of the number covering it, `symbols` marks the symbol columns, so
+// any neighbour lookup is a direct index instead of a scan.
+struct IndexedRow {
+    line: i32,
+    cells: Vec<Option<usize>>,
+    symbols: Vec<bool>,
+}
 
-        is_adj
+impl IndexedRow {
+    fn new(line: i32, width: usize) -> IndexedRow {
+        IndexedRow {
+            line,
+            cells: vec![None; width],
+            symbols: vec![false; width],
+        }
     }
-}
 
-impl SymPosition {
-    fn is_adjacent_to_possible(&self, possible: &PossibleNum) -> bool {
-        let mut is_adj = false;
-        for i in possible.start..=possible.end {
-            if self.line == possible.line {
-                if self.idx == i - 1 || self.idx == i + 1 {
-                    is_adj = true;
-                    break;
-                }
-            } else if self.line == possible.line - 1 || self.line == possible.line + 1 {
-                if i - 1 == self.idx || i == self.idx || i + 1 == self.idx {
-                    is_adj = true;
-                    break;
-                }
-            }
+    fn num_at(&self, idx: i32) -> Option<usize> {
+        if idx < 0 {
+            return None;
         }
-        is_adj
+        self.cells.get(idx as usize).copied().flatten()
+    }
+
+    fn sym_at(&self, idx: i32) -> bool {
+        idx >= 0 && self.symbols.get(idx as usize).copied().unwrap_or(false)
     }
 }
 
-struct State {
-    possible_nums: Vec<PossibleNum>,
+// Everything the renderer needs to reprint the schematic. It grows with the
+// whole input, so it is only collected when a render is requested.
+struct History {
+    lines: Vec<String>,
     valid_nums: Vec<PossibleNum>,
+    rejected_nums: Vec<PossibleNum>,
     symbols: Vec<SymPosition>,
+    gears: Vec<(SymPosition, i32, i32)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CellKind {
+    Plain,
+    Accepted,
+    Rejected,
+    Symbol,
+    Gear,
+}
+
+struct State {
+    // Numbers of the rows still in the window, the number with id `n` lives
+    // at `possible_nums[n - first_id]`
+    possible_nums: VecDeque<PossibleNum>,
+    first_id: usize,
+    part_number_sum: i64,
+    // Gears of the newest row, resolved once the row below is known
     gear_symbols: Vec<SymPosition>,
+    gear_ratio_sum: i64,
+    window: VecDeque<IndexedRow>,
+    history: Option<History>,
 }
 
 impl State {
     fn new() -> State {
         State {
-            possible_nums: Vec::new(),
-            valid_nums: Vec::new(),
-            symbols: Vec::new(),
+            possible_nums: VecDeque::new(),
+            first_id: 0,
+            part_number_sum: 0,
             gear_symbols: Vec::new(),
+            gear_ratio_sum: 0,
+            window: VecDeque::new(),
+            history: None,
         }
     }
 
-    fn process_gears(self) -> i32 {
-        self.gear_symbols
-            .iter()
-            .map(|sym| {
-                self.valid_nums
-                    .iter()
-                    .filter(|num| sym.is_adjacent_to_possible(num))
-                    .map(|num| num.num)
-                    .collect::<Vec<_>>()
-            })
-            .filter(|adjacents_nums| adjacents_nums.len() == 2)
-            .map(|adjacents_nums| adjacents_nums.iter().product::<i32>())
-            .sum()
-    }
-
-    fn update(&mut self, current_line: i32) {
-        // Delete old symbols and numsj
-        self.symbols.retain(|sym| sym.line >= current_line - 1);
-        self.possible_nums
-            .retain(|num| num.line >= current_line - 1);
-
-        // Delete numbs wich no adjacent symbol
-        self.possible_nums.retain(|possible| {
-            let adjacent = self
-                .symbols
-                .iter()
-                .any(|sym| possible.is_adjacent_to_sym(&sym));
-
-            // Add adjacent numbers to valid vector
+    fn with_history() -> State {
+        State {
+            history: Some(History {
+                lines: Vec::new(),
+                valid_nums: Vec::new(),
+                rejected_nums: Vec::new(),
+                symbols: Vec::new(),
+                gears: Vec::new(),
+            }),
+            ..State::new()
+        }
+    }
+
+    fn row(&self, line: i32) -> Option<&IndexedRow> {
+        self.window.iter().find(|row| row.line == line)
+    }
+
+    fn num_mut(&mut self, id: usize) -> &mut PossibleNum {
+        &mut self.possible_nums[id - self.first_id]
+    }
+
+    fn process_gears(&mut self, gears: Vec<SymPosition>) {
+        for gear in gears {
+            let mut adjacent_ids: Vec<usize> = Vec::new();
+            for line in gear.line - 1..=gear.line + 1 {
+                let Some(row) = self.row(line) else {
+                    continue;
+                };
+                for idx in gear.idx - 1..=gear.idx + 1 {
+                    if let Some(id) = row.num_at(idx) {
+                        if !adjacent_ids.contains(&id) {
+                            adjacent_ids.push(id);
+                        }
+                    }
+                }
+            }
+
+            if let [a, b] = adjacent_ids[..] {
+                let a = self.possible_nums[a - self.first_id].num;
+                let b = self.possible_nums[b - self.first_id].num;
+                self.gear_ratio_sum += a as i64 * b as i64;
+                if let Some(history) = &mut self.history {
+                    history.gears.push((gear, a, b));
+                }
+            }
+        }
+    }
+
+    fn retire_rows(&mut self, keep_from_line: i32) {
+        while self
+            .window
+            .front()
+            .is_some_and(|row| row.line < keep_from_line)
+        {
+            self.window.pop_front();
+        }
+
+        // Numbers leaving the window can no longer meet a symbol
+        while self
+            .possible_nums
+            .front()
+            .is_some_and(|num| num.line < keep_from_line)
+        {
+            let num = self.possible_nums.pop_front().unwrap();
+            self.first_id += 1;
+            if num.valid {
+                self.part_number_sum += num.num as i64;
+            }
+            if let Some(history) = &mut self.history {
+                if num.valid {
+                    history.valid_nums.push(num);
+                } else {
+                    history.rejected_nums.push(num);
+                }
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        current_line: i32,
+        row: IndexedRow,
+        gears: Vec<SymPosition>,
+        first_new: usize,
+    ) {
+        self.window.push_back(row);
+
+        // Numbers of this line touching a symbol on this line or the previous one
+
+        for id in first_new..self.first_id + self.possible_nums.len() {
+            let (start, end) = {
+                let num = &self.possible_nums[id - self.first_id];
+                (num.start, num.end)
+            };
+            let adjacent = [current_line - 1, current_line].iter().any(|&line| {
+                self.row(line)
+                    .is_some_and(|r| (start - 1..=end + 1).any(|idx| r.sym_at(idx)))
+            });
             if adjacent {
-                self.valid_nums.push(PossibleNum {
-                    end: possible.end,
-                    start: possible.start,
-                    line: possible.line,
-                    num: possible.num,
-                });
+                self.num_mut(id).valid = true;
+            }
+        }
+
+        // Numbers of the previous line touching a symbol on this line
+        let mut newly_valid = Vec::new();
+        if let (Some(prev), Some(current)) = (self.row(current_line - 1), self.row(current_line)) {
+            for (idx, _) in current.symbols.iter().enumerate().filter(|(_, s)| **s) {
+                let idx = idx as i32;
+                newly_valid.extend((idx - 1..=idx + 1).filter_map(|i| prev.num_at(i)));
             }
+        }
+        for id in newly_valid {
+            self.num_mut(id).valid = true;
+        }
+
+        // The previous line now has both neighbours, so its gears are final
+        let pending = std::mem::replace(&mut self.gear_symbols, gears);
+        self.process_gears(pending);
+
+        self.retire_rows(current_line - 1);
+    }
 
-            !adjacent
-        });
+    fn finish(&mut self) {
+        let pending = std::mem::take(&mut self.gear_symbols);
+        self.process_gears(pending);
+        self.retire_rows(i32::MAX);
     }
 
     pub fn process_line(&mut self, line: &str, line_num: i32) {
+        let mut row = IndexedRow::new(line_num, line.chars().count());
+        let mut gears = Vec::new();
+        let first_new = self.first_id + self.possible_nums.len();
         let mut processing_digit: bool = false;
         let mut possible_start: i32 = 0;
         let mut possible_str = String::new();
         let mut possible_start_setted = false;
-        for (i, c) in line.chars().enumerate() {
-            if c.is_digit(10) {
+        for (i, c) in line.chars().chain(std::iter::once('.')).enumerate() {
+            if c.is_ascii_digit() {
                 processing_digit = true;
                 if !possible_start_setted {
                     possible_start = i as i32;
@@ -125,12 +237,15 @@ impl State {
                 possible_str.push(c);
             } else {
                 if !c.is_alphabetic() && c != '.' {
-                    self.symbols.push(SymPosition {
-                        idx: i as i32,
-                        line: line_num,
-                    });
+                    row.symbols[i] = true;
+                    if let Some(history) = &mut self.history {
+                        history.symbols.push(SymPosition {
+                            idx: i as i32,
+                            line: line_num,
+                        });
+                    }
                     if c == '*' {
-                        self.gear_symbols.push(SymPosition {
+                        gears.push(SymPosition {
                             idx: i as i32,
                             line: line_num,
                         });
@@ -138,11 +253,16 @@ impl State {
                 }
                 if processing_digit {
                     processing_digit = false;
-                    self.possible_nums.push(PossibleNum {
+                    let id = self.first_id + self.possible_nums.len();
+                    for cell in &mut row.cells[possible_start as usize..i] {
+                        *cell = Some(id);
+                    }
+                    self.possible_nums.push_back(PossibleNum {
                         end: i as i32 - 1,
                         start: possible_start,
                         line: line_num,
                         num: possible_str.parse::<i32>().unwrap(),
+                        valid: false,
                     });
                     possible_start = 0;
                     possible_str.truncate(0);
@@ -151,40 +271,194 @@ impl State {
             }
         }
 
-        // Add lasting number
-        if processing_digit {
-            self.possible_nums.push(PossibleNum {
-                end: line.len() as i32 - 1,
-                start: possible_start,
-                line: line_num,
-                num: possible_str.parse::<i32>().unwrap(),
-            });
+        if let Some(history) = &mut self.history {
+            history.lines.push(line.to_string());
+        }
+        self.update(line_num, row, gears, first_new);
+    }
+
+    fn classify(&self) -> Option<Vec<Vec<CellKind>>> {
+        let history = self.history.as_ref()?;
+        let mut kinds: Vec<Vec<CellKind>> = history
+            .lines
+            .iter()
+            .map(|line| vec![CellKind::Plain; line.chars().count()])
+            .collect();
+
+        let nums = history
+            .valid_nums
+            .iter()
+            .map(|num| (num, CellKind::Accepted))
+            .chain(
+                history
+                    .rejected_nums
+                    .iter()
+                    .map(|num| (num, CellKind::Rejected)),
+            );
+        for (num, kind) in nums {
+            for idx in num.start..=num.end {
+                kinds[num.line as usize][idx as usize] = kind;
+            }
         }
-        self.update(line_num);
+        for sym in &history.symbols {
+            kinds[sym.line as usize][sym.idx as usize] = CellKind::Symbol;
+        }
+        for (gear, _, _) in &history.gears {
+            kinds[gear.line as usize][gear.idx as usize] = CellKind::Gear;
+        }
+
+        Some(kinds)
+    }
+
+    fn render_ansi(&self) -> Option<String> {
+        let history = self.history.as_ref()?;
+        let kinds = self.classify()?;
+        let mut out = String::new();
+
+        for (line, line_kinds) in history.lines.iter().zip(&kinds) {
+            let mut last_kind = None;
+            for (c, kind) in line.chars().zip(line_kinds) {
+                if last_kind != Some(*kind) {
+                    let colour = match kind {
+                        CellKind::Plain => "\x1b[0;2m",
+                        CellKind::Accepted => "\x1b[0;32m",
+                        CellKind::Rejected => "\x1b[0;31m",
+                        CellKind::Symbol => "\x1b[0;36m",
+                        CellKind::Gear => "\x1b[1;33m",
+                    };
+                    out.push_str(colour);
+                    last_kind = Some(*kind);
+                }
+                out.push(c);
+            }
+            out.push_str("\x1b[0m\n");
+        }
+
+        out.push('\n');
+        for (gear, a, b) in &history.gears {
+            out.push_str(&format!(
+                "\x1b[1;33mgear\x1b[0m at line {}, column {}: {} * {} = {}\n",
+                gear.line + 1,
+                gear.idx + 1,
+                a,
+                b,
+                *a as i64 * *b as i64
+            ));
+        }
+
+        Some(out)
+    }
+
+    fn render_html(&self) -> Option<String> {
+        let history = self.history.as_ref()?;
+        let kinds = self.classify()?;
+        let ratios: HashMap<(i32, i32), (i32, i32)> = history
+            .gears
+            .iter()
+            .map(|(gear, a, b)| ((gear.line, gear.idx), (*a, *b)))
+            .collect();
+
+        let mut out = String::from(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>Engine schematic</title>\n<style>\n",
+            "body { background: #0f0f23; color: #666; }\n",
+            ".accepted { color: #0c0; }\n",
+            ".rejected { color: #e33; }\n",
+            ".symbol { color: #3cc; }\n",
+            ".gear { color: #ff0; font-weight: bold; }\n",
+            "</style>\n</head>\n<body>\n<pre>\n"
+        ));
+
+        for (line_num, (line, line_kinds)) in history.lines.iter().zip(&kinds).enumerate() {
+            let mut last_kind = CellKind::Plain;
+            for (idx, (c, kind)) in line.chars().zip(line_kinds).enumerate() {
+                // Every gear gets its own span so it can carry its ratio
+                if *kind != last_kind || *kind == CellKind::Gear {
+                    if last_kind != CellKind::Plain {
+                        out.push_str("</span>");
+                    }
+                    match kind {
+                        CellKind::Plain => {}
+                        CellKind::Accepted => out.push_str("<span class=\"accepted\">"),
+                        CellKind::Rejected => out.push_str("<span class=\"rejected\">"),
+                        CellKind::Symbol => out.push_str("<span class=\"symbol\">"),
+                        CellKind::Gear => {
+                            let (a, b) = ratios[&(line_num as i32, idx as i32)];
+                            out.push_str(&format!(
+                                "<span class=\"gear\" title=\"{} * {} = {}\">",
+                                a,
+                                b,
+                                a as i64 * b as i64
+                            ));
+                        }
+                    }
+                    last_kind = *kind;
+                }
+                match c {
+                    '&' => out.push_str("&amp;"),
+                    '<' => out.push_str("&lt;"),
+                    '>' => out.push_str("&gt;"),
+                    _ => out.push(c),
+                }
+            }
+            if last_kind != CellKind::Plain {
+                out.push_str("</span>");
+            }
+            out.push('\n');
+        }
+        out.push_str("</pre>\n<ul>\n");
+
+        for (gear, a, b) in &history.gears {
+            out.push_str(&format!(
+                "<li>gear at line {}, column {}: {} * {} = {}</li>\n",
+                gear.line + 1,
+                gear.idx + 1,
+                a,
+                b,
+                *a as i64 * *b as i64
+            ));
+        }
+        out.push_str("</ul>\n</body>\n</html>\n");
+
+        Some(out)
     }
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day3 <filename>");
+        println!("Usage: ./day3 <filename> [--ansi | --html <output>]");
         std::process::exit(1);
     }
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
-    let mut state = State::new();
+    let render = args.get(2).map(|s| s.as_str());
+    let mut state = match render {
+        Some("--ansi") | Some("--html") => State::with_history(),
+        _ => State::new(),
+    };
 
     for (i, line) in reader.lines().enumerate() {
         state.process_line(&line?, i as i32);
     }
+    state.finish();
 
-    let mut result1 = 0;
-    for number in &state.valid_nums {
-        result1 += number.num;
-    }
+    let result1 = state.part_number_sum;
 
-    let result2 = state.process_gears();
+    let result2 = state.gear_ratio_sum;
+
+    match render {
+        Some("--ansi") => print!("{}", state.render_ansi().unwrap()),
+        Some("--html") => {
+            let Some(output) = args.get(3) else {
+                println!("Usage: ./day3 <filename> --html <output>");
+                std::process::exit(1);
+            };
+            std::fs::write(output, state.render_html().unwrap())?;
+        }
+        _ => {}
+    }
 
     println!("Result for part 1: {}", result1);
     println!("Result for part 2: {}", result2);
@@ -8,7 +8,7 @@ struct Card {
     actual: Vec<u32>,
     points: u32,
     matches: u32,
-    instances: u32,
+    instances: u64,
 }
 
 impl Card {
@@ -83,33 +83,172 @@ impl Card {
     }
 }
 
-const TOTAL_CARDS: usize = 200;
+struct CopyEdge {
+    from: usize,
+    to: usize,
+    copies: u64,
+}
+
+// Copy cascade of part 2: which card spawned how many copies of which later
+// cards. Nodes are positions in the card table.
+struct Cascade {
+    ids: Vec<u32>,
+    instances: Vec<u64>,
+    edges: Vec<CopyEdge>,
+    // Instances spawned, directly or transitively, by a single instance
+    descendants: Vec<u64>,
+}
+
+impl Cascade {
+    fn new(cards: &[Card]) -> Cascade {
+        let mut edges = Vec::new();
+        for (i, card) in cards.iter().enumerate() {
+            let last_won = (i + card.matches as usize).min(cards.len() - 1);
+            for to in i + 1..=last_won {
+                edges.push(CopyEdge {
+                    from: i,
+                    to,
+                    copies: card.instances,
+                });
+            }
+        }
+
+        // Cards only copy later cards, so walking backwards sees every
+        // child before its parents
+        let mut descendants = vec![0; cards.len()];
+        for (i, card) in cards.iter().enumerate().rev() {
+            let last_won = (i + card.matches as usize).min(cards.len() - 1);
+            descendants[i] = (i + 1..=last_won).map(|j| 1 + descendants[j]).sum();
+        }
+
+        Cascade {
+            ids: cards.iter().map(|card| card.id).collect(),
+            instances: cards.iter().map(|card| card.instances).collect(),
+            edges,
+            descendants,
+        }
+    }
+
+    fn position_of(&self, id: u32) -> Option<usize> {
+        self.ids.iter().position(|&card_id| card_id == id)
+    }
+
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cascade {\n");
+        for (i, id) in self.ids.iter().enumerate() {
+            out.push_str(&format!(
+                "    {} [label=\"Card {}\\n{} instances\"];\n",
+                id, id, self.instances[i]
+            ));
+        }
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    {} -> {} [label=\"{}\"];\n",
+                self.ids[edge.from], self.ids[edge.to], edge.copies
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let cards = self
+            .ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let won = self
+                    .edges
+                    .iter()
+                    .filter(|edge| edge.from == i)
+                    .map(|edge| {
+                        format!(
+                            "{{\"card\":{},\"copies\":{}}}",
+                            self.ids[edge.to], edge.copies
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!(
+                    "{{\"id\":{},\"instances\":{},\"descendants\":{},\"won\":[{}]}}",
+                    id, self.instances[i], self.descendants[i], won
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!("{{\"cards\":[\n{}\n]}}\n", cards)
+    }
+}
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day4 <filename>");
+        println!("Usage: ./day4 <filename> [--dot | --json | --descendants <card id>]");
         std::process::exit(1);
     }
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
-    // Lookup table
-    let mut copies_pool: [u32; TOTAL_CARDS] = [0; TOTAL_CARDS];
+    let mut cards = Vec::new();
+    for line in reader.lines() {
+        cards.push(Card::new_from_line(&line?));
+    }
+
+    // Lookup table, indexed by the position of the card in the table
+    let mut copies_pool: Vec<u64> = vec![0; cards.len()];
 
     let mut result1 = 0;
-    for (_i, line) in reader.lines().enumerate() {
-        let mut card = Card::new_from_line(&line?);
-        card.instances += copies_pool[card.id as usize];
-        copies_pool[card.id as usize] += 1;
-        for copy_won in 0..card.matches {
-            // The problem ensures that the index is within limits
-            copies_pool[(card.id + copy_won + 1) as usize] += card.instances;
+    for (i, card) in cards.iter_mut().enumerate() {
+        card.instances += copies_pool[i];
+        copies_pool[i] += 1;
+
+        let last_won = i + card.matches as usize;
+        if last_won >= copies_pool.len() {
+            eprintln!(
+                "warning: card {} would copy {} cards past the end of the table",
+                card.id,
+                last_won + 1 - copies_pool.len()
+            );
+        }
+        for copies in copies_pool.iter_mut().take(last_won + 1).skip(i + 1) {
+            *copies += card.instances;
         }
         result1 += card.points;
     }
 
-    let result2: u32 = copies_pool.iter().sum();
+    let result2: u64 = copies_pool.iter().sum();
+
+    match args.get(2).map(|s| s.as_str()) {
+        // Exports keep stdout clean so they can be piped into other tools
+        Some("--dot") => {
+            print!("{}", Cascade::new(&cards).to_dot());
+            return Ok(());
+        }
+        Some("--json") => {
+            print!("{}", Cascade::new(&cards).to_json());
+            return Ok(());
+        }
+        Some("--descendants") => {
+            let cascade = Cascade::new(&cards);
+            let Some(i) = args
+                .get(3)
+                .and_then(|id| id.parse::<u32>().ok())
+                .and_then(|id| cascade.position_of(id))
+            else {
+                println!("Usage: ./day4 <filename> --descendants <card id>");
+                std::process::exit(1);
+            };
+            println!(
+                "Card {}: {} instances, each one spawns {} descendants ({} in total)",
+                cascade.ids[i],
+                cascade.instances[i],
+                cascade.descendants[i],
+                cascade.instances[i] * cascade.descendants[i]
+            );
+        }
+        _ => {}
+    }
+
     println!("Result for part 1: {}", result1);
     println!("Result for part 2: {}", result2);
 
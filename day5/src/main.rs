@@ -2,230 +2,702 @@ use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
+// Piece of an `IntervalMap`: every value in `start..=end` is shifted by
+// `offset`
+#[derive(Clone, Copy)]
+struct Segment {
+    start: u64,
+    end: u64,
+    offset: i128,
+}
+
+impl Segment {
+    fn apply(&self, value: u64) -> u64 {
+        (value as i128 + self.offset) as u64
+    }
+
+    fn image(&self) -> (u64, u64) {
+        (self.apply(self.start), self.apply(self.end))
+    }
+}
+
+// Piecewise-linear function over the whole u64 domain. Segments are sorted,
+// contiguous and cover `0..=u64::MAX`, values outside any rule simply get
+// an offset of 0.
 #[derive(Clone)]
-struct ConvertionMap {
-    name: String,
-    source_ranges: Vec<(u64, u64)>,
-    destination_ranges: Vec<(u64, u64)>,
-    len: Vec<u64>,
-    total_rules: u64,
+struct IntervalMap {
+    segments: Vec<Segment>,
 }
 
-impl ConvertionMap {
-    fn new() -> ConvertionMap {
-        ConvertionMap {
-            name: String::new(),
-            source_ranges: Vec::new(),
-            destination_ranges: Vec::new(),
-            len: Vec::new(),
-            total_rules: 0,
+impl IntervalMap {
+    fn identity() -> IntervalMap {
+        IntervalMap {
+            segments: vec![Segment {
+                start: 0,
+                end: u64::MAX,
+                offset: 0,
+            }],
         }
     }
 
-    fn reset(&mut self) {
-        self.name = String::new();
-        self.source_ranges = Vec::new();
-        self.destination_ranges = Vec::new();
-        self.len = Vec::new();
-        self.total_rules = 0;
+    fn from_rules(rules: &[Rule]) -> IntervalMap {
+        let mut map = IntervalMap::identity();
+        // Painting backwards leaves the first matching rule on top
+        for rule in rules.iter().rev() {
+            if let Some((start, end)) = rule.source_range() {
+                map.overwrite(start, end, rule.destination as i128 - rule.source as i128);
+            }
+        }
+        map.merge();
+        map
     }
 
-    fn handle_raw_line(&mut self, line: &str) {
-        if line.find(":").is_some() {
-            self.update_name_from_line(line);
-        } else {
-            self.add_map_rule_from_line(line);
+    fn overwrite(&mut self, start: u64, end: u64, offset: i128) {
+        let mut segments = Vec::with_capacity(self.segments.len() + 2);
+        for segment in &self.segments {
+            if segment.end < start || segment.start > end {
+                segments.push(*segment);
+                continue;
+            }
+            if segment.start < start {
+                segments.push(Segment {
+                    end: start - 1,
+                    ..*segment
+                });
+            }
+            if segment.start <= start {
+                segments.push(Segment { start, end, offset });
+            }
+            if segment.end > end {
+                segments.push(Segment {
+                    start: end + 1,
+                    ..*segment
+                });
+            }
         }
+        self.segments = segments;
     }
 
-    fn update_name_from_line(&mut self, line: &str) {
-        let mut splited = line.split_whitespace();
-        self.name = splited.next().unwrap().trim().to_string();
+    // Join neighbouring segments sharing the same offset
+    fn merge(&mut self) {
+        let mut segments: Vec<Segment> = Vec::with_capacity(self.segments.len());
+        for segment in &self.segments {
+            match segments.last_mut() {
+                Some(last) if last.offset == segment.offset => last.end = segment.end,
+                _ => segments.push(*segment),
+            }
+        }
+        self.segments = segments;
     }
 
-    fn add_map_rule_from_line(&mut self, line: &str) {
-        let mut values = line.split_whitespace().map(|s| s.parse::<u64>().unwrap());
+    fn segment_of(&self, value: u64) -> &Segment {
+        let i = self.segments.partition_point(|segment| segment.end < value);
+        &self.segments[i]
+    }
 
-        if let (Some(destination), Some(source), Some(len)) =
-            (values.next(), values.next(), values.next())
-        {
-            self.source_ranges.push((source, source + len - 1));
-            self.destination_ranges
-                .push((destination, destination + len - 1));
-            self.len.push(len);
-            self.total_rules += 1;
-        } else {
-            eprintln!("Error: not enough values");
-        }
+    fn apply(&self, value: u64) -> u64 {
+        self.segment_of(value).apply(value)
     }
 
-    fn convert(&self, seed: u64) -> u64 {
-        let mut new_seed = seed;
-        for i in 0..self.total_rules {
-            let i = i as usize;
-            let sstart = self.source_ranges[i].0;
-            let dstart = self.destination_ranges[i].0;
-            let send = self.source_ranges[i].1;
+    // Pieces of `range` on which the map is a single shift, in domain order
+    fn split_range(&self, range: (u64, u64)) -> impl Iterator<Item = Segment> + '_ {
+        let first = self
+            .segments
+            .partition_point(|segment| segment.end < range.0);
+        self.segments[first..]
+            .iter()
+            .take_while(move |segment| segment.start <= range.1)
+            .map(move |segment| Segment {
+                start: segment.start.max(range.0),
+                end: segment.end.min(range.1),
+                offset: segment.offset,
+            })
+    }
 
-            if new_seed >= sstart && new_seed <= send {
-                new_seed = new_seed - sstart + dstart;
-            }
+    // Lowest value the map takes over `range`
+    fn min_over(&self, range: (u64, u64)) -> Option<u64> {
+        self.split_range(range)
+            .map(|piece| piece.apply(piece.start))
+            .min()
+    }
 
-            if new_seed != seed {
-                break;
+    // `self` followed by `next`
+    fn then(&self, next: &IntervalMap) -> IntervalMap {
+        let mut segments = Vec::new();
+        for segment in &self.segments {
+            for piece in next.split_range(segment.image()) {
+                segments.push(Segment {
+                    start: (piece.start as i128 - segment.offset) as u64,
+                    end: (piece.end as i128 - segment.offset) as u64,
+                    offset: segment.offset + piece.offset,
+                });
             }
         }
-        new_seed
+        let mut map = IntervalMap { segments };
+        map.merge();
+        map
     }
 
-    fn convert_range(&self, range: &(u64, u64)) -> Vec<(u64, u64)> {
-        let mut changed: Vec<(u64, u64)> = Vec::new();
-        let mut unchanged: Vec<(u64, u64)> = vec![*range];
+    // All the values mapped onto `value`
+    fn preimage(&self, value: u64) -> Vec<u64> {
+        self.segments
+            .iter()
+            .filter(|segment| {
+                let (start, end) = segment.image();
+                value >= start && value <= end
+            })
+            .map(|segment| (value as i128 - segment.offset) as u64)
+            .collect()
+    }
 
-        for source_range in &self.source_ranges {
-            let mut new_ranges: Vec<(u64, u64)> = Vec::new();
-            for current_range in &unchanged {
-                if source_range.1 < current_range.0 || source_range.0 > current_range.1 {
-                    // No intersection, add the current range to unchanged
-                    new_ranges.push(*current_range);
-                    continue;
+    // The inverse function, or None when two values share the same image
+    fn invert(&self) -> Option<IntervalMap> {
+        let mut segments: Vec<Segment> = self
+            .segments
+            .iter()
+            .map(|segment| {
+                let (start, end) = segment.image();
+                Segment {
+                    start,
+                    end,
+                    offset: -segment.offset,
                 }
+            })
+            .collect();
+        segments.sort_by_key(|segment| segment.start);
+
+        // The domain is the whole u64 range, so the images must tile it too
+        if segments
+            .windows(2)
+            .any(|pair| pair[0].end.checked_add(1) != Some(pair[1].start))
+        {
+            return None;
+        }
 
-                let intersection = (
-                    source_range.0.max(current_range.0),
-                    source_range.1.min(current_range.1),
-                );
+        let mut map = IntervalMap { segments };
+        map.merge();
+        Some(map)
+    }
+}
+
+#[derive(Clone)]
+struct Rule {
+    destination: u64,
+    source: u64,
+    len: u64,
+}
+
+impl Rule {
+    fn source_range(&self) -> Option<(u64, u64)> {
+        if self.len == 0 {
+            return None;
+        }
+        Some((self.source, self.source + (self.len - 1)))
+    }
+}
+
+// Piece of a range converted by a single rule, or passed through when
+// `rule` is None
+struct Step {
+    source: (u64, u64),
+    destination: (u64, u64),
+    rule: Option<usize>,
+}
+
+enum RuleIssue {
+    Overlap {
+        first: usize,
+        second: usize,
+        range: (u64, u64),
+    },
+    Gap((u64, u64)),
+}
 
-                // Add the converted intersection to changed
-                changed.push((self.convert(intersection.0), self.convert(intersection.1)));
+#[derive(Clone)]
+struct ConvertionMap {
+    name: String,
+    rules: Vec<Rule>,
+}
+
+impl ConvertionMap {
+    fn new(name: &str) -> ConvertionMap {
+        ConvertionMap {
+            name: name.to_string(),
+            rules: Vec::new(),
+        }
+    }
 
-                // Add any left-over range before the intersection
-                if current_range.0 < intersection.0 {
-                    new_ranges.push((current_range.0, intersection.0 - 1));
+    // Source and destination categories of an `x-to-y` name
+    fn categories(&self) -> Option<(&str, &str)> {
+        self.name.split_once("-to-")
+    }
+
+    fn add_map_rule_from_line(&mut self, line: &str, line_num: usize) -> Result<(), AlmanacError> {
+        let values = parse_numbers(line, line_num)?;
+        let [destination, source, len] = values[..] else {
+            return Err(AlmanacError::MalformedRule { line: line_num });
+        };
+
+        // Both ends of the rule must fit in u64, zero-length rules never match
+        let last_offset = len.saturating_sub(1);
+        if source.checked_add(last_offset).is_none()
+            || destination.checked_add(last_offset).is_none()
+        {
+            return Err(AlmanacError::RangeOverflow { line: line_num });
+        }
+
+        self.rules.push(Rule {
+            destination,
+            source,
+            len,
+        });
+        Ok(())
+    }
+
+    fn interval_map(&self) -> IntervalMap {
+        IntervalMap::from_rules(&self.rules)
+    }
+
+    // Index of the first rule covering `seed`
+    fn rule_for(&self, seed: u64) -> Option<usize> {
+        self.rules.iter().position(|rule| {
+            rule.source_range()
+                .is_some_and(|(start, end)| seed >= start && seed <= end)
+        })
+    }
+
+    fn convert(&self, seed: u64) -> u64 {
+        match self.rule_for(seed) {
+            Some(i) => seed - self.rules[i].source + self.rules[i].destination,
+            None => seed,
+        }
+    }
+
+    fn convert_range(&self, range: &(u64, u64)) -> Vec<Step> {
+        // Cut the range wherever a rule starts or ends, each piece is then
+        // handled by a single rule
+        let mut cuts: Vec<u64> = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.source_range())
+            .flat_map(|(start, end)| [Some(start), end.checked_add(1)])
+            .flatten()
+            .filter(|&cut| cut > range.0 && cut <= range.1)
+            .collect();
+        cuts.push(range.0);
+        cuts.sort();
+        cuts.dedup();
+
+        cuts.iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = cuts.get(i + 1).map_or(range.1, |next| next - 1);
+                Step {
+                    source: (start, end),
+                    destination: (self.convert(start), self.convert(end)),
+                    rule: self.rule_for(start),
                 }
+            })
+            .collect()
+    }
 
-                // Add any left-over range after the intersection
-                if intersection.1 < current_range.1 {
-                    new_ranges.push((intersection.1 + 1, current_range.1));
+    fn describe_rule(&self, rule: Option<usize>) -> String {
+        match rule {
+            Some(i) => format!(
+                "rule {}: {} {} {}",
+                i, self.rules[i].destination, self.rules[i].source, self.rules[i].len
+            ),
+            None => "passed through".to_string(),
+        }
+    }
+
+    // Rules whose sources overlap, where the first one wins, and holes
+    // between rules, where values pass through unchanged
+    fn issues(&self) -> Vec<RuleIssue> {
+        let mut issues = Vec::new();
+        let ranges: Vec<(usize, (u64, u64))> = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter_map(|(i, rule)| Some((i, rule.source_range()?)))
+            .collect();
+
+        for (a, &(first, first_range)) in ranges.iter().enumerate() {
+            for &(second, second_range) in &ranges[a + 1..] {
+                let range = (
+                    first_range.0.max(second_range.0),
+                    first_range.1.min(second_range.1),
+                );
+                if range.0 <= range.1 {
+                    issues.push(RuleIssue::Overlap {
+                        first,
+                        second,
+                        range,
+                    });
                 }
             }
-            unchanged = new_ranges;
         }
 
-        // If no unchanged ranges and no changed ranges, add the original range
-        if unchanged.is_empty() && changed.is_empty() {
-            unchanged.push(*range);
+        let mut sorted: Vec<(u64, u64)> = ranges.iter().map(|&(_, range)| range).collect();
+        sorted.sort();
+        let mut covered_until = sorted.first().map(|range| range.1);
+        for range in sorted.iter().skip(1) {
+            let until = covered_until.unwrap();
+            if range.0 > until.saturating_add(1) {
+                issues.push(RuleIssue::Gap((until + 1, range.0 - 1)));
+            }
+            covered_until = Some(until.max(range.1));
         }
 
-        unchanged.extend(changed);
-        unchanged
+        issues
     }
 }
 
-struct Seed {
-    id: u64,
-    range: (u64, u64),
+enum AlmanacError {
+    Io(std::io::Error),
+    InvalidNumber { line: usize, value: String },
+    MalformedRule { line: usize },
+    RuleOutsideMap { line: usize },
+    RangeOverflow { line: usize },
+    UnexpectedLine { line: usize },
+    DuplicateSeeds { line: usize },
+    MissingSeeds,
+    OddSeedCount,
+    SeedRangeOverflow { start: u64, len: u64 },
+    BadMapName { name: String },
+    BrokenChain { expected: String, name: String },
 }
 
-fn parse_seeds(seeds_line: &str) -> Vec<Seed> {
-    let splited = seeds_line.split(":");
-    let seeds_str = splited.last().unwrap().split_whitespace();
+impl std::fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AlmanacError::Io(err) => write!(f, "{}", err),
+            AlmanacError::InvalidNumber { line, value } => {
+                write!(f, "line {}: invalid number '{}'", line, value)
+            }
+            AlmanacError::MalformedRule { line } => {
+                write!(f, "line {}: a rule needs exactly 3 values", line)
+            }
+            AlmanacError::RuleOutsideMap { line } => {
+                write!(f, "line {}: rule found before any map header", line)
+            }
+            AlmanacError::RangeOverflow { line } => {
+                write!(f, "line {}: range goes past {}", line, u64::MAX)
+            }
+            AlmanacError::UnexpectedLine { line } => write!(f, "line {}: unexpected line", line),
+            AlmanacError::DuplicateSeeds { line } => {
+                write!(f, "line {}: seeds are listed twice", line)
+            }
+            AlmanacError::MissingSeeds => write!(f, "no seeds line found"),
+            AlmanacError::OddSeedCount => {
+                write!(f, "seed ranges need an even number of values")
+            }
+            AlmanacError::SeedRangeOverflow { start, len } => {
+                write!(f, "seed range {} {} goes past {}", start, len, u64::MAX)
+            }
+            AlmanacError::BadMapName { name } => {
+                write!(
+                    f,
+                    "map '{}' is not named as <source>-to-<destination>",
+                    name
+                )
+            }
+            AlmanacError::BrokenChain { expected, name } => write!(
+                f,
+                "map '{}' does not convert from '{}', the output of the previous map",
+                name, expected
+            ),
+        }
+    }
+}
 
-    let mut seeds: Vec<Seed> = Vec::new();
+impl From<std::io::Error> for AlmanacError {
+    fn from(err: std::io::Error) -> AlmanacError {
+        AlmanacError::Io(err)
+    }
+}
 
-    for (i, seed) in seeds_str.clone().enumerate() {
-        if i % 2 == 0 {
-            // For result2
-            let range_start = seed.trim().parse::<u64>().unwrap();
-            let range_length = seeds_str
-                .clone()
-                .nth(i + 1)
-                .unwrap()
-                .trim()
+#[derive(Clone, Copy)]
+enum SeedMode {
+    // Every value is a seed on its own
+    List,
+    // Values are (start, length) pairs
+    Ranges,
+}
+
+fn parse_numbers(text: &str, line_num: usize) -> Result<Vec<u64>, AlmanacError> {
+    text.split_whitespace()
+        .map(|value| {
+            value
                 .parse::<u64>()
-                .unwrap();
+                .map_err(|_| AlmanacError::InvalidNumber {
+                    line: line_num,
+                    value: value.to_string(),
+                })
+        })
+        .collect()
+}
+
+struct Almanac {
+    seeds: Vec<u64>,
+    maps: Vec<ConvertionMap>,
+}
+
+impl Almanac {
+    fn parse<R: BufRead>(reader: R) -> Result<Almanac, AlmanacError> {
+        let mut seeds: Option<Vec<u64>> = None;
+        let mut maps: Vec<ConvertionMap> = Vec::new();
+        // Rules are only accepted right after a header, until a blank line
+        let mut in_map = false;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            let line_num = i + 1;
+
+            if line.is_empty() {
+                in_map = false;
+            } else if let Some(values) = line.strip_prefix("seeds:") {
+                if seeds.is_some() {
+                    return Err(AlmanacError::DuplicateSeeds { line: line_num });
+                }
+                seeds = Some(parse_numbers(values, line_num)?);
+            } else if let Some(header) = line.strip_suffix("map:") {
+                maps.push(ConvertionMap::new(header.trim()));
+                in_map = true;
+            } else if line.contains(':') {
+                return Err(AlmanacError::UnexpectedLine { line: line_num });
+            } else if !in_map {
+                return Err(AlmanacError::RuleOutsideMap { line: line_num });
+            } else {
+                maps.last_mut()
+                    .unwrap()
+                    .add_map_rule_from_line(line, line_num)?;
+            }
+        }
 
-            seeds.push(Seed {
-                id: range_start,
-                range: (range_start, range_start + range_length - 1),
-            });
+        let almanac = Almanac {
+            seeds: seeds.ok_or(AlmanacError::MissingSeeds)?,
+            maps,
+        };
+        almanac.validate_chain()?;
+        Ok(almanac)
+    }
+
+    // Each map has to start from the category the previous one produced,
+    // beginning with seeds
+    fn validate_chain(&self) -> Result<(), AlmanacError> {
+        let mut expected = "seed";
+        for map in &self.maps {
+            let Some((source, destination)) = map.categories() else {
+                return Err(AlmanacError::BadMapName {
+                    name: map.name.clone(),
+                });
+            };
+            if source != expected {
+                return Err(AlmanacError::BrokenChain {
+                    expected: expected.to_string(),
+                    name: map.name.clone(),
+                });
+            }
+            expected = destination;
+        }
+        Ok(())
+    }
+
+    // Inclusive seed ranges, zero-length ranges are dropped
+    fn seed_ranges(&self, mode: SeedMode) -> Result<Vec<(u64, u64)>, AlmanacError> {
+        match mode {
+            SeedMode::List => Ok(self.seeds.iter().map(|&seed| (seed, seed)).collect()),
+            SeedMode::Ranges => {
+                if !self.seeds.len().is_multiple_of(2) {
+                    return Err(AlmanacError::OddSeedCount);
+                }
+                let mut ranges = Vec::new();
+                for pair in self.seeds.chunks(2) {
+                    if let Some(range) = seed_range(pair[0], pair[1])? {
+                        ranges.push(range);
+                    }
+                }
+                Ok(ranges)
+            }
         }
     }
+}
+
+fn seed_range(start: u64, len: u64) -> Result<Option<(u64, u64)>, AlmanacError> {
+    if len == 0 {
+        return Ok(None);
+    }
+    match start.checked_add(len - 1) {
+        Some(end) => Ok(Some((start, end))),
+        None => Err(AlmanacError::SeedRangeOverflow { start, len }),
+    }
+}
+
+fn trace_seed(maps: &[ConvertionMap], seed: u64) {
+    println!("seed {}", seed);
+    let mut value = seed;
+    for map in maps {
+        let converted = map.convert(value);
+        println!(
+            "  {}: {} -> {} ({})",
+            map.name,
+            value,
+            converted,
+            map.describe_rule(map.rule_for(value))
+        );
+        value = converted;
+    }
+}
 
-    seeds
+fn trace_range(maps: &[ConvertionMap], range: (u64, u64)) {
+    println!("seeds {}..={}", range.0, range.1);
+    let mut ranges = vec![range];
+    for map in maps {
+        println!("  {}:", map.name);
+        let mut new_ranges = Vec::new();
+        for range in &ranges {
+            for step in map.convert_range(range) {
+                println!(
+                    "    {}..={} -> {}..={} ({})",
+                    step.source.0,
+                    step.source.1,
+                    step.destination.0,
+                    step.destination.1,
+                    map.describe_rule(step.rule)
+                );
+                new_ranges.push(step.destination);
+            }
+        }
+        ranges = new_ranges;
+    }
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day5 <filename>");
+        println!(
+            "Usage: ./day5 <filename> [--report | --seed-of <location> | --trace <seed> [<len>]] [--seeds list|ranges]"
+        );
         std::process::exit(1);
     }
+    // Both parts unless a single way of reading the seeds line is asked for
+    let seed_modes = match args.iter().position(|arg| arg == "--seeds") {
+        None => vec![SeedMode::List, SeedMode::Ranges],
+        Some(i) => match args.get(i + 1).map(|s| s.as_str()) {
+            Some("list") => vec![SeedMode::List],
+            Some("ranges") => vec![SeedMode::Ranges],
+            _ => {
+                println!("Usage: ./day5 <filename> --seeds list|ranges");
+                std::process::exit(1);
+            }
+        },
+    };
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
-    let mut seeds: Vec<Seed> = Vec::new();
-
-    // Load the maps
-    let mut first_line = true;
-    let mut maps: Vec<ConvertionMap> = Vec::new();
-    let mut current_map: ConvertionMap = ConvertionMap::new();
-    for line in reader.lines() {
-        let line = line?;
-        if first_line {
-            seeds = parse_seeds(&line);
-            first_line = false;
-            continue;
+    let almanac = match Almanac::parse(reader) {
+        Ok(almanac) => almanac,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
         }
-        if line.trim().is_empty() {
-            maps.push(current_map.clone());
-            current_map.reset();
-        } else {
-            current_map.handle_raw_line(&line);
+    };
+    let maps = &almanac.maps;
+
+    // Whole almanac folded into a single seed to location function
+    let composed = maps.iter().fold(IntervalMap::identity(), |acc, map| {
+        acc.then(&map.interval_map())
+    });
+
+    match args.get(2).map(|s| s.as_str()) {
+        Some("--report") => {
+            for map in maps {
+                for issue in map.issues() {
+                    match issue {
+                        RuleIssue::Overlap {
+                            first,
+                            second,
+                            range,
+                        } => println!(
+                            "{}: rules {} and {} overlap on {}..={}, rule {} wins",
+                            map.name, first, second, range.0, range.1, first
+                        ),
+                        RuleIssue::Gap(range) => println!(
+                            "{}: no rule for {}..={}, values pass through",
+                            map.name, range.0, range.1
+                        ),
+                    }
+                }
+            }
+            println!("seed-to-location: {} segments", composed.segments.len());
         }
-    }
-
-    // Lasting one
-    if maps.iter().last().unwrap().name != current_map.name {
-        maps.push(current_map.clone());
-    }
-
-    let converted_unique_seeds: Vec<u64> = seeds
-        .iter()
-        .map(|seed| maps.iter().fold(seed.id, |acc, map| map.convert(acc)))
-        .collect();
-
-    let result1: u64 = *converted_unique_seeds.iter().min().unwrap_or(&0) as u64;
-    println!("Result for part 1: {}", result1);
-
-    let mut ranges: Vec<(u64, u64)> = Vec::new();
-    for seed in &seeds {
-        if seed.range.0 == 0 {
-            continue;
+        Some("--seed-of") => {
+            let Some(location) = args.get(3).and_then(|s| s.parse::<u64>().ok()) else {
+                println!("Usage: ./day5 <filename> --seed-of <location>");
+                std::process::exit(1);
+            };
+            match composed.invert() {
+                Some(inverse) => {
+                    println!(
+                        "Location {} comes from seed {}",
+                        location,
+                        inverse.apply(location)
+                    )
+                }
+                None => println!(
+                    "The almanac is not one-to-one, location {} comes from seeds {:?}",
+                    location,
+                    composed.preimage(location)
+                ),
+            }
         }
+        Some("--trace") => {
+            let Some(seed) = args.get(3).and_then(|s| s.parse::<u64>().ok()) else {
+                println!("Usage: ./day5 <filename> --trace <seed> [<len>]");
+                std::process::exit(1);
+            };
+            match args.get(4).and_then(|s| s.parse::<u64>().ok()) {
+                Some(len) => match seed_range(seed, len) {
+                    Ok(Some(range)) => trace_range(maps, range),
+                    Ok(None) => println!("seeds {} {}: empty range", seed, len),
+                    Err(err) => eprintln!("Error: {}", err),
+                },
+                None => trace_seed(maps, seed),
+            }
+        }
+        _ => {}
+    }
 
-        let mut temp_ranges: Vec<(u64, u64)> = vec![seed.range];
-
-        for map in &maps {
-            if map.name.trim().is_empty() {
-                continue;
+    for mode in seed_modes {
+        // An odd seed count only rules out part 2, part 1 is already printed
+        let seeds = match almanac.seed_ranges(mode) {
+            Ok(seeds) => seeds,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        };
+        match mode {
+            SeedMode::List => {
+                let result1: u64 = seeds
+                    .iter()
+                    .map(|seed| maps.iter().fold(seed.0, |acc, map| map.convert(acc)))
+                    .min()
+                    .unwrap_or(0);
+                println!("Result for part 1: {}", result1);
             }
-            let mut new_ranges: Vec<(u64, u64)> = Vec::new();
-            while let Some(range) = temp_ranges.pop() {
-                let converted_ranges = map.convert_range(&range);
-                new_ranges.extend(converted_ranges);
+            SeedMode::Ranges => {
+                let result2: u64 = seeds
+                    .iter()
+                    .filter_map(|&range| composed.min_over(range))
+                    .min()
+                    .unwrap_or(0);
+                println!("Result for part 2: {}", result2);
             }
-            temp_ranges.extend(new_ranges.clone());
         }
-
-        ranges.extend(temp_ranges);
     }
 
-    ranges.sort();
-
-    let result2: u64 = ranges.iter().min_by_key(|&(start, _end)| start).unwrap().0;
-
-    println!("Result for part 2: {}", result2);
-
     Ok(())
 }
@@ -2,18 +2,165 @@ use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-fn time_pressed_from_distance(distance: i64, time_limit: i64) -> Result<(f64, f64), &'static str> {
-    let discriminant: i128 = ((time_limit * time_limit) - (4 * distance))
-        .try_into()
-        .unwrap();
-
-    if discriminant >= 0 {
-        let sqrt_discriminant = (discriminant as f64).sqrt();
-        let t1 = -(-time_limit as f64 + sqrt_discriminant) / 2.0;
-        let t2 = -(-time_limit as f64 - sqrt_discriminant) / 2.0;
-        Ok((t1.floor(), t2.ceil()))
-    } else {
-        Err("No real roots")
+// Inclusive range of hold times beating `distance`, that is every h with
+// h * (time_limit - h) > distance. Everything is done in integers so big
+// part 2 races stay exact.
+fn time_pressed_from_distance(distance: u64, time_limit: u64) -> Result<(u64, u64), &'static str> {
+    let time_limit = time_limit as u128;
+    let distance = distance as u128;
+    let beats = |h: u128| h * (time_limit - h) > distance;
+
+    let square = time_limit * time_limit;
+    if square < 4 * distance {
+        return Err("No real roots");
+    }
+
+    // isqrt may land one step away from the real root, so nudge it
+    let mut first = (time_limit - (square - 4 * distance).isqrt()) / 2;
+    while first <= time_limit / 2 && !beats(first) {
+        first += 1;
+    }
+    while first > 0 && beats(first - 1) {
+        first -= 1;
+    }
+
+    if first > time_limit / 2 || !beats(first) {
+        return Err("No winning hold time");
+    }
+
+    // The distance curve is symmetric around time_limit / 2
+    Ok((first as u64, (time_limit - first) as u64))
+}
+
+// How far a boat gets given how long the button was held. Distance must grow
+// then shrink with the hold time, which lets the default solver search for
+// the peak and then for both edges of the winning interval.
+trait Physics {
+    fn distance(&self, hold: u64, time_limit: u64) -> f64;
+
+    fn winning_holds(&self, distance: u64, time_limit: u64) -> Result<(u64, u64), &'static str> {
+        let reach = |h: u64| self.distance(h, time_limit);
+        let beats = |h: u64| reach(h) > distance as f64;
+
+        let (mut lo, mut hi) = (0, time_limit);
+        while hi - lo > 2 {
+            let m1 = lo + (hi - lo) / 3;
+            let m2 = hi - (hi - lo) / 3;
+            if reach(m1) < reach(m2) {
+                lo = m1 + 1;
+            } else {
+                hi = m2;
+            }
+        }
+        let peak = (lo..=hi)
+            .max_by(|&a, &b| reach(a).total_cmp(&reach(b)))
+            .unwrap();
+        if !beats(peak) {
+            return Err("No winning hold time");
+        }
+
+        // First winning hold, the distance is increasing up to the peak
+        let (mut lo, mut hi) = (0, peak);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if beats(mid) {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        let first = lo;
+
+        // Last winning hold, the distance is decreasing after the peak
+        let (mut lo, mut hi) = (peak, time_limit);
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if beats(mid) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        Ok((first, lo))
+    }
+}
+
+// Speed grows by `accel` for every ms held, the puzzle uses an accel of 1
+struct Linear {
+    accel: u64,
+}
+
+impl Physics for Linear {
+    fn distance(&self, hold: u64, time_limit: u64) -> f64 {
+        (self.accel as u128 * hold as u128 * (time_limit - hold) as u128) as f64
+    }
+
+    // accel * x > d holds exactly when x > d / accel, so the puzzle solver
+    // still gives the exact answer
+    fn winning_holds(&self, distance: u64, time_limit: u64) -> Result<(u64, u64), &'static str> {
+        time_pressed_from_distance(distance / self.accel, time_limit)
+    }
+}
+
+// Holding past `limit` ms wastes time without adding any speed
+struct ChargeLimited {
+    accel: u64,
+    limit: u64,
+}
+
+impl Physics for ChargeLimited {
+    fn distance(&self, hold: u64, time_limit: u64) -> f64 {
+        let speed = self.accel as f64 * hold.min(self.limit) as f64;
+        speed * (time_limit - hold) as f64
+    }
+}
+
+// Water slows the boat down, the speed decays as exp(-drag * t) after release
+struct Drag {
+    accel: u64,
+    drag: f64,
+}
+
+impl Physics for Drag {
+    fn distance(&self, hold: u64, time_limit: u64) -> f64 {
+        let speed = self.accel as f64 * hold as f64;
+        let travel = (time_limit - hold) as f64;
+        if self.drag == 0.0 {
+            return speed * travel;
+        }
+        speed * (1.0 - (-self.drag * travel).exp()) / self.drag
+    }
+}
+
+fn physics_from_args(args: &[String]) -> Result<Box<dyn Physics>, String> {
+    let mut accel: u64 = 1;
+    let mut limit: Option<u64> = None;
+    let mut drag: Option<f64> = None;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", option))?;
+        let invalid = || format!("Invalid value for {}: {}", option, value);
+        match option.as_str() {
+            "--accel" => accel = value.parse().map_err(|_| invalid())?,
+            "--charge-limit" => limit = Some(value.parse().map_err(|_| invalid())?),
+            "--drag" => drag = Some(value.parse().map_err(|_| invalid())?),
+            _ => return Err(format!("Unknown option {}", option)),
+        }
+    }
+
+    if accel == 0 {
+        return Err("The acceleration must be positive".to_string());
+    }
+    match (limit, drag) {
+        (Some(_), Some(_)) => Err("Pick either --charge-limit or --drag".to_string()),
+        (Some(limit), None) => Ok(Box::new(ChargeLimited { accel, limit })),
+        (None, Some(drag)) if drag >= 0.0 => Ok(Box::new(Drag { accel, drag })),
+        (None, Some(_)) => Err("The drag must not be negative".to_string()),
+        (None, None) => Ok(Box::new(Linear { accel })),
     }
 }
 
@@ -27,18 +174,30 @@ fn parse_line(line: &str) -> Vec<u64> {
         .collect()
 }
 
-fn join_to_number(vec: Vec<u64>) -> i64 {
+fn join_to_number(vec: Vec<u64>) -> Result<u64, &'static str> {
     let joined_string: String = vec.iter().map(|&num| num.to_string()).collect();
-    let joined_number: i64 = joined_string.parse().unwrap_or(0);
-    joined_number
+    joined_string
+        .parse()
+        .map_err(|_| "Joined number does not fit in u64")
+}
+
+fn ways_to_win(range: Result<(u64, u64), &'static str>) -> u64 {
+    range.map_or(0, |(first, last)| last - first + 1)
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day6 <filename>");
+        println!("Usage: ./day6 <filename> [--accel <n>] [--charge-limit <ms> | --drag <k>]");
         std::process::exit(1);
     }
+    let physics = match physics_from_args(&args[2..]) {
+        Ok(physics) => physics,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
@@ -47,22 +206,35 @@ fn main() -> std::io::Result<()> {
     let records = parse_line(&lines.next().unwrap().unwrap());
 
     let mut result1: u64 = 1;
-    for i in 0..times.len() {
-        let extremes = time_pressed_from_distance(
-            records[i].try_into().unwrap(),
-            times[i].try_into().unwrap(),
-        )
-        .unwrap();
-        let possibles = extremes.1 - extremes.0 - 1.0;
-        result1 *= possibles as u64;
+    for (i, (&time, &record)) in times.iter().zip(&records).enumerate() {
+        let winning = physics.winning_holds(record, time);
+        match winning {
+            Ok((first, last)) => println!(
+                "Race {}: hold from {} to {} ms ({} ways)",
+                i + 1,
+                first,
+                last,
+                last - first + 1
+            ),
+            Err(err) => println!("Race {}: {}", i + 1, err),
+        }
+        result1 *= ways_to_win(winning);
     }
 
-    let big_time_limit = join_to_number(times);
-    let big_record_meters = join_to_number(records);
-    let extremes_of_single_race =
-        time_pressed_from_distance(big_record_meters, big_time_limit).unwrap();
+    let (big_time_limit, big_record_meters) = match (join_to_number(times), join_to_number(records))
+    {
+        (Ok(time), Ok(record)) => (time, record),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let winning_of_single_race = physics.winning_holds(big_record_meters, big_time_limit);
+    if let Ok((first, last)) = winning_of_single_race {
+        println!("Single race: hold from {} to {} ms", first, last);
+    }
 
-    let result2 = extremes_of_single_race.1 - extremes_of_single_race.0 - 1.0;
+    let result2 = ways_to_win(winning_of_single_race);
 
     println!("Result for part 1: {}", result1);
     println!("Result for part 2: {}", result2);
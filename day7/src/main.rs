@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
@@ -11,237 +12,578 @@ enum HandType {
     OnePair,
     TwoPair,
     ThreeOfAKind,
+    Straight,
+    Flush,
     FullHouse,
     FourOfAKind,
+    StraightFlush,
     FiveOfAKind,
 }
 
-impl HandType {
-    #[allow(dead_code)]
-    fn to_string(self) -> String {
-        match self {
-            HandType::None => String::from("None"),
-            HandType::HighCard => String::from("High Card"),
-            HandType::OnePair => String::from("One Pair"),
-            HandType::TwoPair => String::from("Two Pair"),
-            HandType::ThreeOfAKind => String::from("Three of a Kind"),
-            HandType::FullHouse => String::from("Full House"),
-            HandType::FourOfAKind => String::from("Four of a Kind"),
-            HandType::FiveOfAKind => String::from("Five of a Kind"),
-        }
-    }
-
-    fn get_hand_type(highest_count: u32, second_highest_count: u32) -> HandType {
-        match highest_count {
-            1 => HandType::HighCard,
-            2 => {
-                if second_highest_count == 2 {
-                    HandType::TwoPair
-                } else {
-                    HandType::OnePair
-                }
+impl fmt::Display for HandType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            HandType::None => "None",
+            HandType::HighCard => "High Card",
+            HandType::OnePair => "One Pair",
+            HandType::TwoPair => "Two Pair",
+            HandType::ThreeOfAKind => "Three of a Kind",
+            HandType::Straight => "Straight",
+            HandType::Flush => "Flush",
+            HandType::FullHouse => "Full House",
+            HandType::FourOfAKind => "Four of a Kind",
+            HandType::StraightFlush => "Straight Flush",
+            HandType::FiveOfAKind => "Five of a Kind",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone)]
+struct Ruleset {
+    // Card labels from weakest to strongest, also used for tie-breaks
+    card_order: Vec<char>,
+    // Labels that stand in for whatever card makes the best hand
+    wildcards: Vec<char>,
+    hand_size: usize,
+    // Hand types from weakest to strongest, with the group sizes a hand
+    // needs, largest first, to reach each of them
+    hand_types: Vec<(HandType, Vec<u32>)>,
+}
+
+impl Ruleset {
+    fn new(card_order: &str, wildcards: &str, hand_size: usize) -> Ruleset {
+        Ruleset {
+            card_order: card_order.chars().collect(),
+            wildcards: wildcards.chars().collect(),
+            hand_size,
+            hand_types: vec![
+                (HandType::HighCard, vec![1]),
+                (HandType::OnePair, vec![2]),
+                (HandType::TwoPair, vec![2, 2]),
+                (HandType::ThreeOfAKind, vec![3]),
+                (HandType::FullHouse, vec![3, 2]),
+                (HandType::FourOfAKind, vec![4]),
+                (HandType::FiveOfAKind, vec![5]),
+            ],
+        }
+    }
+
+    fn camel_cards() -> Ruleset {
+        Ruleset::new("23456789TJQKA", "", 5)
+    }
+
+    // J is a joker, and the weakest card when breaking ties
+    fn camel_cards_with_jokers() -> Ruleset {
+        Ruleset::new("J23456789TQKA", "J", 5)
+    }
+
+    fn card_value(&self, card: char) -> Option<usize> {
+        self.card_order.iter().position(|&c| c == card)
+    }
+
+    // Strongest entry of the table matched by the group sizes, sorted from
+    // largest to smallest
+    fn classify(&self, counts: &[u32]) -> Option<usize> {
+        self.hand_types.iter().rposition(|(_, needed)| {
+            needed
+                .iter()
+                .enumerate()
+                .all(|(i, &size)| counts.get(i).is_some_and(|&count| count >= size))
+        })
+    }
+
+    // Groups of equal cards, largest first, then strongest first
+    fn sort_groups(&self, groups: &mut [(u32, char)]) {
+        groups.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| self.card_value(b.1).cmp(&self.card_value(a.1)))
+        });
+    }
+
+    // Tries every way of spreading the wildcards over the groups, either
+    // growing an existing group or starting a new one. Also returns the
+    // labels the wildcards were best treated as.
+    fn classify_with_wildcards(
+        &self,
+        groups: &[(u32, char)],
+        wildcards: u32,
+    ) -> (Option<usize>, Vec<char>) {
+        if wildcards == 0 {
+            let counts: Vec<u32> = groups.iter().map(|group| group.0).collect();
+            return (self.classify(&counts), Vec::new());
+        }
+
+        let mut best: Option<(Option<usize>, Vec<char>)> = None;
+        for i in 0..=groups.len() {
+            // Growing equal groups gives the same hand, keep the strongest
+            if i > 0 && i < groups.len() && groups[i].0 == groups[i - 1].0 {
+                continue;
             }
-            3 => {
-                if second_highest_count == 2 {
-                    HandType::FullHouse
-                } else {
-                    HandType::ThreeOfAKind
+            let mut grown = groups.to_vec();
+            let label = match grown.get_mut(i) {
+                Some(group) => {
+                    group.0 += 1;
+                    group.1
+                }
+                None => {
+                    // A new group takes the strongest label not in the hand
+                    let Some(&label) = self.card_order.iter().rev().find(|label| {
+                        !self.wildcards.contains(label)
+                            && !groups.iter().any(|group| group.1 == **label)
+                    }) else {
+                        continue;
+                    };
+                    grown.push((1, label));
+                    label
                 }
+            };
+            self.sort_groups(&mut grown);
+
+            let (strength, mut labels) = self.classify_with_wildcards(&grown, wildcards - 1);
+            if best.as_ref().is_none_or(|best| strength > best.0) {
+                labels.insert(0, label);
+                best = Some((strength, labels));
             }
-            4 => HandType::FourOfAKind,
-            5 => HandType::FiveOfAKind,
-            _ => HandType::None,
         }
+        best.unwrap_or((None, Vec::new()))
+    }
+}
+
+const POKER_RANKS: &str = "23456789TJQKA";
+const POKER_SUITS: &str = "shdc";
+
+// Poker hand types from weakest to strongest
+const POKER_HAND_TYPES: [HandType; 9] = [
+    HandType::HighCard,
+    HandType::OnePair,
+    HandType::TwoPair,
+    HandType::ThreeOfAKind,
+    HandType::Straight,
+    HandType::Flush,
+    HandType::FullHouse,
+    HandType::FourOfAKind,
+    HandType::StraightFlush,
+];
+
+#[derive(Clone, Copy)]
+struct PokerCard {
+    // 0 for a 2 up to 12 for an ace
+    rank: u8,
+    suit: u8,
+}
+
+fn parse_poker_cards(cards: &str) -> Result<[PokerCard; 5], String> {
+    let chars: Vec<char> = cards.chars().collect();
+    if chars.len() != 10 {
+        return Err(format!("'{}' is not five cards like AsKd...", cards));
+    }
+
+    let mut parsed = [PokerCard { rank: 0, suit: 0 }; 5];
+    for (i, pair) in chars.chunks(2).enumerate() {
+        let rank = POKER_RANKS.find(pair[0]);
+        let suit = POKER_SUITS.find(pair[1]);
+        let (Some(rank), Some(suit)) = (rank, suit) else {
+            return Err(format!("unknown card '{}{}'", pair[0], pair[1]));
+        };
+        if parsed[..i]
+            .iter()
+            .any(|other| other.rank as usize == rank && other.suit as usize == suit)
+        {
+            return Err(format!("card '{}{}' is dealt twice", pair[0], pair[1]));
+        }
+        parsed[i] = PokerCard {
+            rank: rank as u8,
+            suit: suit as u8,
+        };
+    }
+    Ok(parsed)
+}
+
+// Packed poker hand strength: the hand type index in the top bits, then up
+// to five ranks of 4 bits in tie-break order. Comparing scores compares hands.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+struct PokerScore(u32);
+
+impl PokerScore {
+    fn new(type_index: usize, ranks: &[u8]) -> PokerScore {
+        let mut score = (type_index as u32) << 20;
+        for (i, &rank) in ranks.iter().enumerate() {
+            score |= (rank as u32) << (16 - 4 * i);
+        }
+        PokerScore(score)
+    }
+
+    fn type_index(self) -> usize {
+        (self.0 >> 20) as usize
+    }
+
+    fn hand_type(self) -> HandType {
+        POKER_HAND_TYPES[self.type_index()]
+    }
+
+    fn ranks(self) -> Vec<usize> {
+        (0..5)
+            .map(|i| ((self.0 >> (16 - 4 * i)) & 0xF) as usize)
+            .collect()
     }
 }
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+
+// Allocation free evaluation of a five card poker hand
+fn evaluate_poker(cards: &[PokerCard; 5]) -> PokerScore {
+    let mut counts = [0u8; 13];
+    let mut rank_mask: u16 = 0;
+    for card in cards {
+        counts[card.rank as usize] += 1;
+        rank_mask |= 1 << card.rank;
+    }
+    let flush = cards.iter().all(|card| card.suit == cards[0].suit);
+
+    // Highest rank of a five card run, A2345 counts as a five-high straight
+    let straight_high = (4..13u8)
+        .rev()
+        .find(|&high| (rank_mask >> (high - 4)) & 0x1F == 0x1F)
+        .or((rank_mask == 0b1_0000_0000_1111).then_some(3));
+
+    // Ranks ordered by group size, then by rank, which is the kicker order
+    let mut groups = [(0u8, 0u8); 5];
+    let mut len = 0;
+    for rank in (0..13u8).rev() {
+        if counts[rank as usize] > 0 {
+            groups[len] = (counts[rank as usize], rank);
+            len += 1;
+        }
+    }
+    let groups = &mut groups[..len];
+    groups.sort_by_key(|group| std::cmp::Reverse(group.0));
+    let mut ranks = [0u8; 5];
+    for (rank, group) in ranks.iter_mut().zip(groups.iter()) {
+        *rank = group.1;
+    }
+    let ranks = &ranks[..len];
+
+    let hand_type = match (
+        straight_high,
+        flush,
+        groups[0].0,
+        groups.get(1).map(|g| g.0),
+    ) {
+        (Some(_), true, _, _) => HandType::StraightFlush,
+        (_, _, 4, _) => HandType::FourOfAKind,
+        (_, _, 3, Some(2)) => HandType::FullHouse,
+        (_, true, _, _) => HandType::Flush,
+        (Some(_), _, _, _) => HandType::Straight,
+        (_, _, 3, _) => HandType::ThreeOfAKind,
+        (_, _, 2, Some(2)) => HandType::TwoPair,
+        (_, _, 2, _) => HandType::OnePair,
+        (_, _, 1, _) => HandType::HighCard,
+        // Only four suits, the parser already rejects a card dealt twice
+        _ => unreachable!("five cards of one rank"),
+    };
+    let type_index = POKER_HAND_TYPES
+        .iter()
+        .position(|&t| t == hand_type)
+        .unwrap();
+
+    match (hand_type, straight_high) {
+        (HandType::StraightFlush | HandType::Straight, Some(high)) => {
+            PokerScore::new(type_index, &[high])
+        }
+        _ => PokerScore::new(type_index, ranks),
+    }
+}
+
 struct Hand {
-    id: u32,
     cards: String,
     bid: u32,
-    rank: u32,
+    rank: usize,
     hand_type: HandType,
-    hand_type_with_joker: HandType,
-}
-
-const CARD_VALUES_SIZE: usize = 265;
-const CARD_VALUES: [u32; CARD_VALUES_SIZE] = {
-    let mut values = [0; CARD_VALUES_SIZE];
-    values['A' as usize] = 14;
-    values['K' as usize] = 13;
-    values['Q' as usize] = 12;
-    values['J' as usize] = 11;
-    values['T' as usize] = 10;
-    values['9' as usize] = 9;
-    values['8' as usize] = 8;
-    values['7' as usize] = 7;
-    values['6' as usize] = 6;
-    values['5' as usize] = 5;
-    values['4' as usize] = 4;
-    values['3' as usize] = 3;
-    values['2' as usize] = 2;
-    values
-};
+    // Position of `hand_type` in the ruleset table
+    strength: Option<usize>,
+    card_values: Vec<usize>,
+    // Labels the wildcards of the hand were best treated as, in order
+    substitutions: Vec<char>,
+}
 
 impl Hand {
-    fn new() -> Hand {
-        Hand {
-            id: 0,
-            cards: String::new(),
-            bid: 0,
-            rank: 0,
-            hand_type: HandType::None,
-            hand_type_with_joker: HandType::None,
+    fn new_from_line(line: &str, id: u32, ruleset: &Ruleset) -> Result<Hand, String> {
+        let mut splited_line = line.split_whitespace();
+        let cards = splited_line.next().unwrap_or_default().to_string();
+        let bid = splited_line
+            .next()
+            .and_then(|bid| bid.parse::<u32>().ok())
+            .ok_or_else(|| format!("line {}: missing or invalid bid", id + 1))?;
+
+        if cards.chars().count() != ruleset.hand_size {
+            return Err(format!(
+                "line {}: hand '{}' does not have {} cards",
+                id + 1,
+                cards,
+                ruleset.hand_size
+            ));
         }
-    }
+        let card_values = cards
+            .chars()
+            .map(|card| {
+                ruleset
+                    .card_value(card)
+                    .ok_or_else(|| format!("line {}: unknown card '{}'", id + 1, card))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-    fn new_from_line(line: &str, id: u32) -> Hand {
-        let mut splited_line = line.split_whitespace();
-        let mut hand = Hand::new();
-        hand.id = id;
-        hand.cards = splited_line.next().unwrap().to_string();
-        hand.bid = splited_line.next().unwrap().parse::<u32>().unwrap();
-        hand.classify_type();
-        hand
+        let mut hand = Hand {
+            cards,
+            bid,
+            rank: 0,
+            hand_type: HandType::None,
+            strength: None,
+            card_values,
+            substitutions: Vec::new(),
+        };
+        hand.classify_type(ruleset);
+        Ok(hand)
     }
 
-    fn classify_type(&mut self) {
-        let mut value_counts: HashMap<char, usize> = HashMap::new();
+    fn classify_type(&mut self, ruleset: &Ruleset) {
+        let mut value_counts: HashMap<char, u32> = HashMap::new();
+        let mut wildcards: u32 = 0;
 
         for card in self.cards.chars() {
-            let count = value_counts.entry(card).or_insert(0);
-            *count += 1;
+            if ruleset.wildcards.contains(&card) {
+                wildcards += 1;
+            } else {
+                *value_counts.entry(card).or_insert(0) += 1;
+            }
         }
 
-        let mut highest_count: u32 = 0;
-        let mut second_highest_count: u32 = 0;
+        let mut groups: Vec<(u32, char)> = value_counts
+            .into_iter()
+            .map(|(card, count)| (count, card))
+            .collect();
+        ruleset.sort_groups(&mut groups);
 
-        let mut highest_count_j: u32 = 0;
-        let mut second_highest_count_j: u32 = 0;
-
-        value_counts.iter().for_each(|(&card, &count)| {
-            let count_u32 = count as u32;
+        (self.strength, self.substitutions) = ruleset.classify_with_wildcards(&groups, wildcards);
+        self.hand_type = self
+            .strength
+            .map_or(HandType::None, |i| ruleset.hand_types[i].0);
+    }
 
-            if count_u32 > highest_count {
-                second_highest_count = highest_count;
-                highest_count = count_u32;
-            } else if count_u32 > second_highest_count {
-                second_highest_count = count_u32;
-            }
-            if card != 'J' && count_u32 > highest_count_j {
-                second_highest_count_j = highest_count_j;
-                highest_count_j = count_u32;
-            } else if card != 'J' && count_u32 > second_highest_count_j {
-                second_highest_count_j = count_u32;
-            }
-        });
+    fn new_poker_from_line(line: &str, id: u32) -> Result<Hand, String> {
+        let mut splited_line = line.split_whitespace();
+        let cards = splited_line.next().unwrap_or_default().to_string();
+        let bid = splited_line
+            .next()
+            .and_then(|bid| bid.parse::<u32>().ok())
+            .ok_or_else(|| format!("line {}: missing or invalid bid", id + 1))?;
+        let score = evaluate_poker(
+            &parse_poker_cards(&cards).map_err(|err| format!("line {}: {}", id + 1, err))?,
+        );
 
-        let highest_with_joker =
-            highest_count_j + value_counts.get(&'J').map_or(0, |&count| count as u32);
+        Ok(Hand {
+            cards,
+            bid,
+            rank: 0,
+            hand_type: score.hand_type(),
+            strength: Some(score.type_index()),
+            card_values: score.ranks(),
+            substitutions: Vec::new(),
+        })
+    }
 
-        self.hand_type_with_joker =
-            HandType::get_hand_type(highest_with_joker, second_highest_count_j);
-        self.hand_type = HandType::get_hand_type(highest_count, second_highest_count);
+    fn assign_rank(&mut self, rank: usize) {
+        self.rank = rank;
     }
 
-    fn is_stronger_than(&self, other: &Hand, j_is_joker: bool) -> bool {
-        let self_hand_type: &HandType = if j_is_joker {
-            &self.hand_type_with_joker
-        } else {
-            &self.hand_type
-        };
-        let other_hand_type: &HandType = if j_is_joker {
-            &other.hand_type_with_joker
-        } else {
-            &other.hand_type
-        };
+    // Why the hand got its type, and why it sits above `below`, the hand
+    // ranked right under it
+    fn explain(&self, below: Option<&Hand>, ruleset: &Ruleset) -> String {
+        let mut text = format!("Rank {}: {}", self.rank, self.cards);
 
-        if *self_hand_type as i32 != *other_hand_type as i32 {
-            return *self_hand_type as i32 > *other_hand_type as i32;
+        if !self.substitutions.is_empty() {
+            let wildcards: String = self
+                .cards
+                .chars()
+                .filter(|card| ruleset.wildcards.contains(card))
+                .collect();
+            let labels: String = self.substitutions.iter().collect();
+            text.push_str(&format!(", {} as {}", wildcards, labels));
         }
+        text.push_str(&format!(" -> {}", self.hand_type));
 
-        for (self_card, other_card) in self.cards.chars().zip(other.cards.chars()) {
-            if self_card == other_card {
-                continue;
-            }
+        let Some(below) = below else {
+            text.push_str("; weakest hand");
+            return text;
+        };
+        if below.strength != self.strength {
+            text.push_str(&format!(
+                "; beats {} ({}) on hand type",
+                below.cards, below.hand_type
+            ));
+            return text;
+        }
 
-            let self_card_val = if j_is_joker && self_card == 'J' {
-                1
-            } else {
-                CARD_VALUES[self_card as usize]
-            };
-            let other_card_val = if j_is_joker && other_card == 'J' {
-                1
-            } else {
-                CARD_VALUES[other_card as usize]
-            };
-            return self_card_val > other_card_val;
+        let tie_break = self
+            .cards
+            .chars()
+            .zip(below.cards.chars())
+            .enumerate()
+            .find(|(_, (ours, theirs))| ours != theirs);
+        match tie_break {
+            Some((i, (ours, theirs))) => text.push_str(&format!(
+                "; beats {} on card {}: {} over {}",
+                below.cards,
+                i + 1,
+                ours,
+                theirs
+            )),
+            None => text.push_str(&format!("; ties with {}", below.cards)),
         }
-        false
+        text
     }
+}
 
-    fn assign_rank(&mut self, rank: u32) {
-        self.rank = rank;
+impl Ord for Hand {
+    fn cmp(&self, other: &Hand) -> Ordering {
+        self.strength
+            .cmp(&other.strength)
+            .then_with(|| self.card_values.cmp(&other.card_values))
     }
+}
 
-    fn compare(&self, other: &Hand, j_is_joker: bool) -> Ordering {
-        let self_hand_type: HandType = if j_is_joker {
-            self.hand_type_with_joker
-        } else {
-            self.hand_type
-        };
-        let other_hand_type: HandType = if j_is_joker {
-            other.hand_type_with_joker
-        } else {
-            other.hand_type
-        };
+impl PartialOrd for Hand {
+    fn partial_cmp(&self, other: &Hand) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Hand {
+    fn eq(&self, other: &Hand) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Hand {}
 
-        if self_hand_type != other_hand_type {
-            self_hand_type.cmp(&other_hand_type)
-        } else {
-            self.is_stronger_than(other, j_is_joker).cmp(&true)
+// Poker hands are scored without any ruleset
+fn ranked_hands(lines: &[String], ruleset: Option<&Ruleset>) -> Result<Vec<Hand>, String> {
+    let mut hands: Vec<Hand> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        hands.push(match ruleset {
+            Some(ruleset) => Hand::new_from_line(line, i as u32, ruleset)?,
+            None => Hand::new_poker_from_line(line, i as u32)?,
+        });
+    }
+
+    hands.sort();
+
+    for (index, hand) in hands.iter_mut().enumerate() {
+        hand.assign_rank(index + 1);
+    }
+
+    Ok(hands)
+}
+
+fn total_winnings(lines: &[String], ruleset: Option<&Ruleset>) -> Result<u64, String> {
+    let hands = ranked_hands(lines, ruleset)?;
+
+    // Millions of hands overflow u32 well before the last rank
+    Ok(hands.iter().fold(0, |mut acc, hand| {
+        acc += hand.bid as u64 * hand.rank as u64;
+        acc
+    }))
+}
+
+// Custom rules given on the command line, on top of the part 1 ones
+fn ruleset_from_args(args: &[String]) -> Result<Option<Ruleset>, String> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+
+    let mut ruleset = Ruleset::camel_cards();
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", option))?;
+        match option.as_str() {
+            "--order" => ruleset.card_order = value.chars().collect(),
+            "--wild" => ruleset.wildcards = value.chars().collect(),
+            "--hand-size" => {
+                ruleset.hand_size = value
+                    .parse()
+                    .map_err(|_| format!("Invalid hand size: {}", value))?
+            }
+            _ => return Err(format!("Unknown option {}", option)),
         }
     }
+    Ok(Some(ruleset))
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day7 <filename>");
+        println!(
+            "Usage: ./day7 <filename> [--poker | [--explain] [--order <labels>] [--wild <labels>] [--hand-size <n>]]"
+        );
         std::process::exit(1);
     }
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
-    let mut hands: Vec<Hand> = Vec::new();
-    for (i, line) in reader.lines().enumerate() {
-        hands.push(Hand::new_from_line(&line?, i as u32));
-    }
-
-    hands.sort_by(|a, b| a.compare(b, false));
+    let lines = reader.lines().collect::<std::io::Result<Vec<String>>>()?;
 
-    for (index, hand) in hands.iter_mut().enumerate() {
-        hand.assign_rank(index as u32 + 1);
+    if args.get(2).is_some_and(|arg| arg == "--poker") {
+        match total_winnings(&lines, None) {
+            Ok(result) => println!("Result for poker: {}", result),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
     }
 
-    let result1 = hands.iter().fold(0, |mut acc, hand| {
-        acc += hand.bid * hand.rank;
-        acc
-    });
-
-    hands.sort_by(|a, b| a.compare(b, true));
+    let explain = args.iter().any(|arg| arg == "--explain");
+    let options: Vec<String> = args[2..]
+        .iter()
+        .filter(|arg| *arg != "--explain")
+        .cloned()
+        .collect();
 
-    for (index, hand) in hands.iter_mut().enumerate() {
-        hand.assign_rank(index as u32 + 1);
-    }
+    let results = ruleset_from_args(&options).and_then(|custom| {
+        // Explains the joker rules, or the custom ones when given
+        if explain {
+            let ruleset = custom
+                .clone()
+                .unwrap_or_else(Ruleset::camel_cards_with_jokers);
+            let hands = ranked_hands(&lines, Some(&ruleset))?;
+            for (i, hand) in hands.iter().enumerate() {
+                let below = i.checked_sub(1).map(|below| &hands[below]);
+                println!("{}", hand.explain(below, &ruleset));
+            }
+        }
 
-    let result2 = hands.iter().fold(0, |mut acc, hand| {
-        acc += hand.bid * hand.rank;
-        acc
+        let result1 = total_winnings(&lines, Some(&Ruleset::camel_cards()))?;
+        let result2 = total_winnings(&lines, Some(&Ruleset::camel_cards_with_jokers()))?;
+        let custom_result = match custom {
+            Some(ruleset) => Some(total_winnings(&lines, Some(&ruleset))?),
+            None => None,
+        };
+        Ok((result1, result2, custom_result))
     });
+    let (result1, result2, custom_result) = match results {
+        Ok(results) => results,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
 
     println!("Result for part 1: {}", result1);
     println!("Result for part 2: {}", result2);
+    if let Some(custom_result) = custom_result {
+        println!("Result for custom rules: {}", custom_result);
+    }
 
     Ok(())
 }
@@ -1,68 +1,571 @@
+use std::cmp::Ordering;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-fn diff_vec_of(numbers: &Vec<i32>) -> Vec<i32> {
-    numbers
-        .iter()
-        .zip(numbers.iter().skip(1))
-        .map(|(a, b)| (b - a) as i32)
-        .collect::<Vec<_>>()
+// Signed integer of any size, long histories have differences and
+// coefficients well past i128. The magnitude is little endian without
+// trailing zeros, zero is never negative.
+#[derive(Clone, PartialEq, Eq)]
+struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn magnitude_cmp(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn magnitude_add(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs: Vec<u32> = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry: u64 = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        limbs.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        limbs.push(carry as u32);
+    }
+    limbs
+}
+
+// a - b, with a at least as large as b
+fn magnitude_sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs: Vec<u32> = Vec::with_capacity(a.len());
+    let mut borrow: i64 = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut diff = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (diff < 0) as i64;
+        if diff < 0 {
+            diff += 1 << 32;
+        }
+        limbs.push(diff as u32);
+    }
+    trim(&mut limbs);
+    limbs
+}
+
+fn magnitude_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut limbs = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry: u64 = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let current = limbs[i + j] as u64 + x as u64 * y as u64 + carry;
+            limbs[i + j] = current as u32;
+            carry = current >> 32;
+        }
+        limbs[i + b.len()] = carry as u32;
+    }
+    trim(&mut limbs);
+    limbs
+}
+
+fn magnitude_div_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: u64 = 0;
+    for i in (0..a.len()).rev() {
+        let current = (remainder << 32) | a[i] as u64;
+        quotient[i] = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    trim(&mut quotient);
+    (quotient, remainder as u32)
+}
+
+// Schoolbook binary long division, one bit of the quotient at a time
+fn magnitude_div_rem(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let (quotient, remainder) = magnitude_div_small(a, b[0]);
+        let mut remainder = vec![remainder];
+        trim(&mut remainder);
+        return (quotient, remainder);
+    }
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        // remainder = remainder * 2 + next bit of a
+        let mut carry = a[bit / 32] >> (bit % 32) & 1;
+        for limb in remainder.iter_mut() {
+            let next_carry = *limb >> 31;
+            *limb = (*limb << 1) | carry;
+            carry = next_carry;
+        }
+        if carry > 0 {
+            remainder.push(carry);
+        }
+        if magnitude_cmp(&remainder, b) != Ordering::Less {
+            remainder = magnitude_sub(&remainder, b);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    trim(&mut quotient);
+    (quotient, remainder)
+}
+
+impl BigInt {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        trim(&mut limbs);
+        BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    fn from_i128(n: i128) -> BigInt {
+        let magnitude = n.unsigned_abs();
+        let limbs = (0..4).map(|i| (magnitude >> (32 * i)) as u32).collect();
+        BigInt::new(n < 0, limbs)
+    }
+
+    fn zero() -> BigInt {
+        BigInt::from_i128(0)
+    }
+
+    fn one() -> BigInt {
+        BigInt::from_i128(1)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn abs(&self) -> BigInt {
+        BigInt::new(false, self.limbs.clone())
+    }
+
+    fn neg(&self) -> BigInt {
+        BigInt::new(!self.negative, self.limbs.clone())
+    }
+
+    fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, magnitude_add(&self.limbs, &other.limbs));
+        }
+        match magnitude_cmp(&self.limbs, &other.limbs) {
+            Ordering::Less => BigInt::new(other.negative, magnitude_sub(&other.limbs, &self.limbs)),
+            _ => BigInt::new(self.negative, magnitude_sub(&self.limbs, &other.limbs)),
+        }
+    }
+
+    fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::new(
+            self.negative != other.negative,
+            magnitude_mul(&self.limbs, &other.limbs),
+        )
+    }
+
+    // Quotient truncated towards zero, the remainder takes the sign of self
+    fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+        let (quotient, remainder) = magnitude_div_rem(&self.limbs, &other.limbs);
+        (
+            BigInt::new(self.negative != other.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        )
+    }
+
+    fn div(&self, other: &BigInt) -> BigInt {
+        self.div_rem(other).0
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => magnitude_cmp(&self.limbs, &other.limbs),
+            (true, true) => magnitude_cmp(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Peel off 9 decimal digits at a time, the most a u32 can hold
+        const CHUNK: u32 = 1_000_000_000;
+        let mut limbs = self.limbs.clone();
+        let mut chunks: Vec<u32> = Vec::new();
+        while !limbs.is_empty() {
+            let (quotient, remainder) = magnitude_div_small(&limbs, CHUNK);
+            chunks.push(remainder);
+            limbs = quotient;
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{}", first)?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{:09}", chunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while !b.is_zero() {
+        let remainder = a.div_rem(&b).1;
+        a = b;
+        b = remainder;
+    }
+    a
 }
 
-fn parse_line(line: &str) -> Vec<i32> {
+// Exact fraction, always kept reduced with a positive denominator
+#[derive(Clone, PartialEq)]
+struct Rational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Rational {
+    fn new(num: BigInt, den: BigInt) -> Rational {
+        let mut divisor = gcd(&num, &den);
+        if divisor.is_zero() {
+            divisor = BigInt::one();
+        }
+        if den.negative {
+            divisor = divisor.neg();
+        }
+        Rational {
+            num: num.div(&divisor),
+            den: den.div(&divisor),
+        }
+    }
+
+    fn add(&self, other: &Rational) -> Rational {
+        Rational::new(
+            self.num.mul(&other.den).add(&other.num.mul(&self.den)),
+            self.den.mul(&other.den),
+        )
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.den == BigInt::one() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+enum FitError {
+    Empty,
+    // No difference row within the history reached all zeros. `residual` is
+    // the deepest row still holding two values, and `best_fit` the degree of
+    // the best least squares fit with its root mean square error.
+    NotPolynomial {
+        residual: Vec<BigInt>,
+        best_fit: Option<(usize, f64)>,
+    },
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FitError::Empty => write!(f, "empty history"),
+            FitError::NotPolynomial { residual, best_fit } => {
+                write!(f, "never reaches a zero row, residual row {:?}", residual)?;
+                if let Some((degree, error)) = best_fit {
+                    write!(
+                        f,
+                        ", best least squares fit degree {} (rms error {:.3})",
+                        degree, error
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+// Least squares fits of every degree below len - 1 over the whole history,
+// projecting it on an orthonormal basis of the polynomials sampled at
+// 0..len built one degree at a time. The winner has the lowest residual
+// variance, the sum of squared errors over the len - k - 1 values not used
+// up by the fit, so a higher degree has to earn its extra coefficient.
+// Returns that degree and the root mean square error of its fit.
+fn least_squares_fit(history: &[i64]) -> Option<(usize, f64)> {
+    let len = history.len();
+    let mut residual: Vec<f64> = history.iter().map(|&y| y as f64).collect();
+    let mut basis: Vec<Vec<f64>> = Vec::new();
+    let mut q = vec![1.0 / (len as f64).sqrt(); len];
+    // Degree, residual variance and sum of squared errors
+    let mut best: Option<(usize, f64, f64)> = None;
+
+    for degree in 0..len.saturating_sub(1) {
+        let c = dot(&residual, &q);
+        for (r, q) in residual.iter_mut().zip(&q) {
+            *r -= c * q;
+        }
+        let squared_error = dot(&residual, &residual);
+        let variance = squared_error / (len - degree - 1) as f64;
+        if best.is_none_or(|(_, best_variance, _)| variance < best_variance) {
+            best = Some((degree, variance, squared_error));
+        }
+
+        // Next basis polynomial from x * q, orthogonalised twice as a
+        // single pass leaves rounding errors behind
+        let mut next: Vec<f64> = q.iter().enumerate().map(|(x, q)| x as f64 * q).collect();
+        basis.push(q);
+        for _ in 0..2 {
+            for b in &basis {
+                let c = dot(&next, b);
+                for (n, b) in next.iter_mut().zip(b) {
+                    *n -= c * b;
+                }
+            }
+        }
+        let norm = dot(&next, &next).sqrt();
+        q = next.iter().map(|n| n / norm).collect();
+    }
+
+    best.map(|(degree, _, squared_error)| (degree, (squared_error / len as f64).sqrt()))
+}
+
+// Polynomial through every value of a history, with x being the index in the
+// history. Kept in Newton form, f(x) = sum of d_k * C(x, k), where d_k is the
+// k-th forward difference at x = 0.
+struct Polynomial {
+    differences: Vec<BigInt>,
+}
+
+impl Polynomial {
+    fn fit(history: &[i64]) -> Result<Polynomial, FitError> {
+        if history.is_empty() {
+            return Err(FitError::Empty);
+        }
+
+        // Differences computed in place, after step k the slots from k on
+        // hold the k-th difference row, so slot k holds d_k
+        let mut differences: Vec<BigInt> = history
+            .iter()
+            .map(|&n| BigInt::from_i128(n as i128))
+            .collect();
+        let len = differences.len();
+        let mut residual = differences.clone();
+        let mut converged = false;
+
+        for k in 0..len {
+            if k > 0 {
+                for i in (k..len).rev() {
+                    differences[i] = differences[i].sub(&differences[i - 1]);
+                }
+            }
+            let row = &differences[k..];
+            if row.iter().all(|n| n.is_zero()) {
+                converged = true;
+                break;
+            }
+            if row.len() >= 2 {
+                residual = row.to_vec();
+            }
+        }
+
+        if !converged {
+            return Err(FitError::NotPolynomial {
+                residual,
+                best_fit: least_squares_fit(history),
+            });
+        }
+
+        while differences.last().is_some_and(|n| n.is_zero()) {
+            differences.pop();
+        }
+        Ok(Polynomial { differences })
+    }
+
+    fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+
+    // f(x) in O(degree)
+    fn value_at(&self, x: &BigInt) -> BigInt {
+        let mut value = BigInt::zero();
+        let mut binomial = BigInt::one();
+        for (k, difference) in self.differences.iter().enumerate() {
+            value = value.add(&difference.mul(&binomial));
+            // C(x, k + 1) = C(x, k) * (x - k) / (k + 1), always exact
+            let k = k as i128;
+            binomial = binomial
+                .mul(&x.sub(&BigInt::from_i128(k)))
+                .div(&BigInt::from_i128(k + 1));
+        }
+        value
+    }
+
+    // Value `steps` positions after the last one of a history of `len` values
+    fn extrapolate_forward(&self, len: usize, steps: i128) -> BigInt {
+        let x = BigInt::from_i128(len as i128 - 1).add(&BigInt::from_i128(steps));
+        self.value_at(&x)
+    }
+
+    // Value `steps` positions before the first one
+    fn extrapolate_backward(&self, steps: i128) -> BigInt {
+        self.value_at(&BigInt::from_i128(steps).neg())
+    }
+
+    // Coefficients of 1, x, x^2... by expanding every C(x, k)
+    fn coefficients(&self) -> Vec<Rational> {
+        let zero = Rational::new(BigInt::zero(), BigInt::one());
+        let mut coefficients = vec![zero; self.differences.len().max(1)];
+        // Falling factorial x (x - 1) ... (x - k + 1) and k!
+        let mut falling: Vec<BigInt> = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+
+        for (k, difference) in self.differences.iter().enumerate() {
+            for (coefficient, term) in coefficients.iter_mut().zip(&falling) {
+                *coefficient =
+                    coefficient.add(&Rational::new(difference.mul(term), factorial.clone()));
+            }
+
+            // Multiply the falling factorial by (x - k)
+            let k = BigInt::from_i128(k as i128);
+            let mut next = vec![BigInt::zero(); falling.len() + 1];
+            for (i, term) in falling.iter().enumerate() {
+                next[i + 1] = next[i + 1].add(term);
+                next[i] = next[i].sub(&term.mul(&k));
+            }
+            falling = next;
+            factorial = factorial.mul(&k.add(&BigInt::one()));
+        }
+
+        coefficients
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut written = false;
+        for (power, coefficient) in self.coefficients().iter().enumerate().rev() {
+            if coefficient.num.is_zero() && (power > 0 || written) {
+                continue;
+            }
+            let sign = match (written, coefficient.num.negative) {
+                (false, false) => "",
+                (false, true) => "-",
+                (true, false) => " + ",
+                (true, true) => " - ",
+            };
+            let magnitude = Rational::new(coefficient.num.abs(), coefficient.den.clone());
+            match power {
+                0 => write!(f, "{}{}", sign, magnitude)?,
+                1 => write!(f, "{}{} x", sign, magnitude)?,
+                _ => write!(f, "{}{} x^{}", sign, magnitude, power)?,
+            }
+            written = true;
+        }
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str) -> Vec<i64> {
     line.split_whitespace()
-        .map(|n| n.trim().parse::<i32>().unwrap())
+        .map(|n| n.trim().parse::<i64>().unwrap())
         .collect()
 }
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Usage: ./day9 <filename>");
+        println!("Usage: ./day9 <filename> [--fit] [--steps <n>]");
         std::process::exit(1);
     }
 
     let f = File::open(&args[1])?;
     let reader = BufReader::new(f);
 
-    let mut histories: Vec<Vec<i32>> = Vec::new();
+    let mut histories: Vec<Vec<i64>> = Vec::new();
     for line in reader.lines() {
         histories.push(parse_line(&line?));
     }
 
-    let predictions: Vec<(i32, i32)> = histories
+    let show_fit = args.iter().any(|arg| arg == "--fit");
+    let steps: Option<i128> = args
         .iter()
-        .map(|h| {
-            let mut diffs: Vec<Vec<i32>> = Vec::new();
-            let mut current: &Vec<i32> = &h;
-
-            loop {
-                let diff = diff_vec_of(current);
-                diffs.push(diff.clone());
-                if diff.iter().all(|n| *n == 0) {
-                    break;
-                }
-                current = diffs.last().unwrap();
-            }
+        .position(|arg| arg == "--steps")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|steps| steps.parse().ok());
 
-            let prediction_right = diffs.iter().fold(0 as i32, |mut acc, d| {
-                acc += d.last().unwrap();
-                acc
-            }) + h.last().unwrap();
+    let polynomials: Vec<Result<Polynomial, FitError>> =
+        histories.iter().map(|h| Polynomial::fit(h)).collect();
 
-            let prediction_left = -(diffs.iter().rev().fold(0 as i32, |mut acc, d| {
-                acc = -acc + d.first().unwrap();
-                acc
-            })) + h.first().unwrap();
-
-            (prediction_left, prediction_right)
-        })
-        .collect();
+    for (i, (h, polynomial)) in histories.iter().zip(&polynomials).enumerate() {
+        let polynomial = match polynomial {
+            Ok(polynomial) => polynomial,
+            Err(err) => {
+                println!("History {}: skipped, {}", i + 1, err);
+                continue;
+            }
+        };
+        if show_fit {
+            println!(
+                "History {}: degree {}, f(x) = {}",
+                i + 1,
+                polynomial.degree(),
+                polynomial
+            );
+        }
+        if let Some(steps) = steps {
+            println!(
+                "History {}: {} steps back {}, {} steps ahead {}",
+                i + 1,
+                steps,
+                polynomial.extrapolate_backward(steps),
+                steps,
+                polynomial.extrapolate_forward(h.len(), steps)
+            );
+        }
+    }
 
-    let (result1, result2) = predictions
-        .iter()
-        .fold((0, 0), |(acc1, acc2), p| (acc1 + p.1, acc2 + p.0));
+    let (result1, result2) = histories.iter().zip(&polynomials).fold(
+        (BigInt::zero(), BigInt::zero()),
+        |(acc1, acc2), (h, polynomial)| match polynomial {
+            Ok(polynomial) => (
+                acc1.add(&polynomial.extrapolate_forward(h.len(), 1)),
+                acc2.add(&polynomial.extrapolate_backward(1)),
+            ),
+            Err(_) => (acc1, acc2),
+        },
+    );
 
     println!("Result for part 1: {}", result1);
     println!("Result for part 2: {}", result2);